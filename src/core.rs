@@ -1,15 +1,21 @@
 use std::rc::Rc;
 use std::cell::RefCell;
-use std::time::{SystemTime};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::time::{SystemTime, Instant};
 use std::fs::File;
-use std::io::Read;
-use std::collections::HashMap;
+use std::io::{Read, Write, BufRead, BufReader};
+use std::process::Stdio;
 
 use crate::error;
 use crate::parser;
-use crate::types::{Value, ValueList, Arity, LazyData};
+use crate::types::{self, Value, ValueList, Arity, LazyData, Env, EnvStruct, MapKey};
 use crate::names::{NamePool, Name};
 use crate::printer::Printer;
+use crate::regex::{Regex, Captures};
+use crate::pvec::PVector;
+use crate::pmap::PMap;
+use crate::numeric::{self, Op};
 
 type ValueResult = Result<Value, error::Error>;
 
@@ -18,21 +24,16 @@ macro_rules! type_err {
 }
 
 macro_rules! ord_op {
-    ($op:tt, $v:expr) => {{
-        let mut left = match &$v[0] {
-            Value::Num(n) => *n,
-            x => return type_err!("number", x)
-        };
+    ($($ord:pat)|+, $v:expr) => {{
+        let mut left = &$v[0];
+        if !numeric::is_real(left) {
+            return type_err!("real number", left)
+        }
         for e in $v[1..].iter() {
-            if let Value::Num(n) = e {
-                if left $op *n {
-                    left = *n;
-                    continue
-                }else{
-                    return Ok(Value::False)
-                }
-            }else{
-                return type_err!("number", e)
+            match numeric::compare(left, e) {
+                Some($($ord)|+) => left = e,
+                _ if !numeric::is_real(e) => return type_err!("real number", e),
+                _ => return Ok(Value::False),
             }
         }
         return Ok(Value::True)
@@ -40,34 +41,29 @@ macro_rules! ord_op {
 }
 
 macro_rules! add_mul_op {
-    ($op:tt, $init:expr, $args:expr) => {
-        Ok(Value::Num(
-            $args.iter().fold(Ok($init), |acc, val| if let Value::Num(n) = val {
-                Ok(acc? $op *n)
-            }else{
-                return type_err!("number", val)
-            })?
-        ))
+    ($op:expr, $init:expr, $args:expr) => {
+        $args.iter().try_fold(Value::Num($init), |acc, val| match numeric::arith($op, &acc, val) {
+            Some(res) => Ok(res),
+            None => type_err!("number", val)
+        })
     };
 }
 
+// With a single argument it is applied to the identity, so (- x) is (- 0 x)
 macro_rules! sub_div_op {
-    ($op:tt, $none:expr, $one:expr, $args:expr) => {{
+    ($op:expr, $none:expr, $identity:expr, $args:expr) => {{
         if $args.len() == 0 {
             return $none
         }
-        match &$args[0] {
-            Value::Num(first) => if $args.len() > 1 {
-                Ok(Value::Num($args[1..].iter().fold(Ok(first.clone()), |acc, val| if let Value::Num(n) = val {
-                    Ok(acc? $op *n)
-                }else{
-                    return type_err!("number", val)
-                })?))
-            }else{
-                Ok(Value::Num($one(*first)))
-            }
-            x => type_err!("number", x)
+        let identity = $identity;
+        let (first, rest) = if $args.len() == 1 { (&identity, &$args[..]) } else { (&$args[0], &$args[1..]) };
+        if !numeric::is_number(first) {
+            return type_err!("number", first)
         }
+        rest.iter().try_fold(first.clone(), |acc, val| match numeric::arith($op, &acc, val) {
+            Some(res) => Ok(res),
+            None => type_err!("number", val)
+        })
     }};
 }
 
@@ -88,10 +84,20 @@ macro_rules! predicate_op {
     };
 }
 
+/// Real numbers are equal when they have the same value, so `(== 1/2 0.5)` holds
+/// like `<=` and `>=` do, everything else compares by structure
+fn helper_equal(a: &Value, b: &Value) -> bool {
+    if numeric::is_real(a) && numeric::is_real(b) {
+        numeric::compare(a, b) == Some(Ordering::Equal)
+    } else {
+        a == b
+    }
+}
+
 fn operator_eq(v: ValueList, _names: &NamePool) -> ValueResult {
     let left = &v[0]; 
     for e in v[1..].iter() {
-        if left != e {
+        if !helper_equal(left, e) {
             return Ok(Value::False)
         }
     }
@@ -101,7 +107,7 @@ fn operator_eq(v: ValueList, _names: &NamePool) -> ValueResult {
 fn operator_ne(v: ValueList, _names: &NamePool) -> ValueResult {
     let left = &v[0]; 
     for e in v[1..].iter() {
-        if left == e {
+        if helper_equal(left, e) {
             return Ok(Value::False)
         }
     }
@@ -111,20 +117,35 @@ fn operator_ne(v: ValueList, _names: &NamePool) -> ValueResult {
 fn operator_str(v: ValueList, names: &NamePool) -> ValueResult {
     let mut res = String::new();
     for e in v.iter() {
-        res.push_str(&format!("{}", Printer::str_name(e, names)))
+        helper_push_str(&mut res, e, names);
     }
     return Ok(Value::Str(res.into()));
 }
 
+/// Appends a value the way `str` shows it
+fn helper_push_str(res: &mut String, value: &Value, names: &NamePool) {
+    match value {
+        Value::Str(s) => res.push_str(s.inner()),
+        Value::Char(c) => res.push(*c),
+        e => res.push_str(&Printer::str_name(e, names)),
+    }
+}
+
 pub fn operator_head(v: ValueList, _names: &NamePool) -> ValueResult {
     v[0].first().map_err(From::from)
 }
 
+/// Checks that an index is a finite non-negative integer before using it as `usize`
+fn helper_index(value: &Value) -> Result<usize, error::Error> {
+    match value {
+        Value::Num(n) if n.is_finite() && *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        Value::BigInt(n) if *n >= 0 => Ok(usize::try_from(*n).unwrap_or(usize::MAX)),
+        x => type_err!("non-negative integer", x)
+    }
+}
+
 fn operator_nth(v: ValueList, _names: &NamePool) -> ValueResult {
-    let n = match &v[1] {
-        Value::Num(n) => *n as usize,
-        x => return type_err!("number", x),
-    };
+    let n = helper_index(&v[1])?;
 
     match &v[0] {
         Value::List(l) => {
@@ -135,23 +156,23 @@ fn operator_nth(v: ValueList, _names: &NamePool) -> ValueResult {
             }
         },
         Value::Nil => Ok(Value::Nil),
+        Value::PVector(vector) => Ok(vector.get(n).cloned().into()),
+        Value::Str(s) => Ok(s.inner().chars().nth(n).into()),
         Value::Lazy{env, eval, data} => {
             if n == 0 {
                 return Ok(data.head.clone());
             }
 
             let mut count = n;
-            let mut nth = data.tail.clone();
-            let mut env = env.clone();
+            let mut nth = data.force_tail(env, *eval)?;
             loop {
                 count -= 1;
-                match eval(nth, env.clone(), data.names.clone())? {
-                    Value::Lazy{env: tenv, data, ..} => {
+                match nth {
+                    Value::Lazy{env, eval, data} => {
                         if count == 0 {
                             break Ok((data.head).clone())
                         }else{
-                            nth = (data.tail).clone();
-                            env = tenv;
+                            nth = data.force_tail(&env, eval)?;
                         }
                     }
                     _ => break Ok(Value::Nil)
@@ -183,6 +204,18 @@ fn operator_cons(v: ValueList, _names: &NamePool) -> ValueResult {
     }
 }
 
+// (list* 1 2 [3 4]) is (1 2 3 4), the last argument must be a list
+fn operator_list_star(v: ValueList, _names: &NamePool) -> ValueResult {
+    let (tail, init) = v.split_last().unwrap();
+    let mut new = init.to_vec();
+    match tail {
+        Value::List(l) => new.extend_from_slice(l.inner()),
+        Value::Nil => {},
+        x => return type_err!("list", x)
+    }
+    Ok(new.into())
+}
+
 fn operator_rev_cons(v: ValueList, _names: &NamePool) -> ValueResult {
     match &v[0] {
         Value::List(l) => {
@@ -202,27 +235,29 @@ fn operator_rev_cons(v: ValueList, _names: &NamePool) -> ValueResult {
 }
 
 
-fn core_hashmap(v: ValueList, _names: &NamePool) -> ValueResult {
+fn core_hashmap(v: ValueList, names: &NamePool) -> ValueResult {
     if v.len() % 2 != 0 {
         return Err(error::Error::KwArgErr(Some(crate::names::builtin::HASH_MAP)));
     }
 
-    let mut map: HashMap<Name, Value> = HashMap::default();
+    let mut map: PMap<MapKey, Value> = PMap::new();
 
     for i in (0..v.len()).step_by(2) {
-        match &v[i] {
-            Value::Keyword(s) | Value::Sym(s) => map.insert(*s, v[i+1].clone()),
-            x => return type_err!("keyword", x.clone())
+        match MapKey::from_value(&v[i], names) {
+            Some(key) => map.insert(key, v[i+1].clone()),
+            None => return type_err!("keyword", v[i].clone())
         };
     };
-    Ok(Value::Map(Rc::new(map)))
+    Ok(Value::Map(map))
 }
 
-fn operator_assoc(v: ValueList, _names: &NamePool) -> ValueResult {
-    let mut map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
-    } else {
-        return type_err!("map", v[0]);
+// nil is treated as an empty map
+fn operator_assoc(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut map = match &v[0] {
+        Value::Map(hashmap) => hashmap.clone(),
+        Value::PVector(vector) => return helper_assoc_vector(vector, &v[1..]),
+        Value::Nil => PMap::new(),
+        x => return type_err!("map", x),
     };
 
     let v = &v[1..];
@@ -232,99 +267,230 @@ fn operator_assoc(v: ValueList, _names: &NamePool) -> ValueResult {
     }
 
     for i in (0..v.len()).step_by(2) {
-        match &v[i] {
-            Value::Keyword(s) | Value::Sym(s) => map.insert(*s, v[i+1].clone()),
-            x => return type_err!("keyword", x.clone())
+        match MapKey::from_value(&v[i], names) {
+            Some(key) => map.insert(key, v[i+1].clone()),
+            None => return type_err!("keyword", v[i].clone())
         };
     };
-    Ok(Value::Map(Rc::new(map)))
+    Ok(Value::Map(map))
+}
+
+// Sets vector elements by index, an index one past the end appends
+fn helper_assoc_vector(vector: &PVector, v: &[Value]) -> ValueResult {
+    if !v.len().is_multiple_of(2) {
+        return Err(error::Error::KwArgErr(Some(crate::names::builtin::ASSOC)));
+    }
+    let mut vector = vector.clone();
+    for pair in v.chunks(2) {
+        let index = helper_index(&pair[0])?;
+        vector = if index == vector.len() {
+            vector.push(pair[1].clone())
+        } else {
+            vector.set(index, pair[1].clone()).ok_or_else(|| format!("Index {} is out of bounds for a vector of length {}", index, vector.len()))?
+        };
+    }
+    Ok(Value::PVector(Rc::new(vector)))
 }
 
 fn operator_map_update(v: ValueList, names: &NamePool) -> ValueResult {
     let mut map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
+        hashmap.clone()
     } else {
         return type_err!("map", v[0]);
     };
 
-    let (old, key) = match &v[1] {
-        Value::Keyword(n) | Value::Sym(n) => match map.get(n){
-            Some(v) => (v.clone(), *n),
-            None => (Value::Nil, *n)
+    let (old, key) = match MapKey::from_value(&v[1], names) {
+        Some(key) => match map.get(&key){
+            Some(v) => (v.clone(), key),
+            None => (Value::Nil, key)
         },
-        x => return type_err!("keyword", x.clone())
+        None => return type_err!("keyword", v[1].clone())
     };
     let mut args = vec![old];
     args.extend_from_slice(&v[3..]);
     let new = v[2].apply(args, names)?;
-    map.insert(key.clone(), new);
-    Ok(Value::Map(Rc::new(map)))
+    map.insert(key, new);
+    Ok(Value::Map(map))
 }
 
-fn operator_dissoc(v: ValueList, _names: &NamePool) -> ValueResult {
-    let mut map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
-    } else {
-        return type_err!("map", v[0]);
+fn operator_dissoc(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut map = match &v[0] {
+        Value::Map(hashmap) => hashmap.clone(),
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("map", x),
     };
 
     let v = &v[1..];
 
     for key in v {
-        match key {
-            Value::Keyword(s) | Value::Sym(s) => map.remove(s),
-            x => return type_err!("keyword", x.clone())
+        match MapKey::from_value(key, names) {
+            Some(key) => map.remove(&key),
+            None => return type_err!("keyword", key.clone())
+        };
+    };
+    Ok(Value::Map(map))
+}
+
+// Keys found in several maps are combined left to right with the function,
+// nil maps are skipped and only nils give back nil
+fn core_merge_with(v: ValueList, names: &NamePool) -> ValueResult {
+    let func = &v[0];
+    let mut result: Option<PMap<MapKey, Value>> = None;
+    for arg in v[1..].iter() {
+        let map = match arg {
+            Value::Map(hashmap) => hashmap,
+            Value::Nil => continue,
+            x => return type_err!("map", x)
         };
+        let result = match &mut result {
+            Some(result) => result,
+            None => {
+                result = Some(map.clone());
+                continue
+            }
+        };
+        for (k, val) in map.iter() {
+            let merged = match result.remove(k) {
+                Some(old) => func.apply(vec![old, val.clone()], names)?,
+                None => val.clone(),
+            };
+            result.insert(*k, merged);
+        }
+    }
+    Ok(match result {
+        Some(map) => Value::Map(map),
+        None => Value::Nil,
+    })
+}
+
+// When two keys map to the same new key the pair visited last wins
+fn core_update_keys(v: ValueList, names: &NamePool) -> ValueResult {
+    let map = match &v[0] {
+        Value::Map(hashmap) => hashmap,
+        x => return type_err!("map", x)
     };
-    Ok(Value::Map(Rc::new(map)))
+    let func = &v[1];
+
+    let mut result: PMap<MapKey, Value> = PMap::new();
+    for (k, val) in map.iter() {
+        let new = func.apply(vec![k.to_value(names)], names)?;
+        let key = match MapKey::from_value(&new, names) {
+            Some(key) => key,
+            None => return type_err!("keyword", new)
+        };
+        result.insert(key, val.clone());
+    }
+    Ok(Value::Map(result))
+}
+
+// Converts map keys with `convert`, going into nested maps and lists. When keys
+// collide after the conversion (like :a and "a") only one of the pairs is kept
+fn helper_convert_keys(value: &Value, convert: fn(MapKey) -> MapKey) -> Value {
+    match value {
+        Value::Map(map) => Value::Map(map.iter()
+            .map(|(k, v)| (convert(*k), helper_convert_keys(v, convert)))
+            .collect()),
+        Value::List(l) => l.iter().map(|v| helper_convert_keys(v, convert)).collect::<ValueList>().into(),
+        x => x.clone(),
+    }
+}
+
+fn core_keywordize_keys(v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(helper_convert_keys(&v[0], |key| MapKey::Keyword(key.name())))
+}
+
+fn core_stringify_keys(v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(helper_convert_keys(&v[0], |key| MapKey::Str(key.name())))
 }
 
-fn operator_map_get(v: ValueList, _names: &NamePool) -> ValueResult {
+fn operator_map_get(v: ValueList, names: &NamePool) -> ValueResult {
     let map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
+        hashmap
     } else {
         return type_err!("map", v[0]);
     };
 
-    match &v[1] {
-        Value::Keyword(s) | Value::Sym(s) => match map.get(s){
+    match MapKey::from_value(&v[1], names) {
+        Some(key) => match map.get(&key){
             Some(v) => Ok(v.clone()),
-            None => Err(error::Error::KeyErr(*s))
+            None => Err(error::Error::KeyErr(key.name()))
         },
-        x => return type_err!("keyword", x.clone())
+        None => type_err!("keyword", v[1].clone())
     }
 }
 
-fn operator_has_key(v: ValueList, _names: &NamePool) -> ValueResult {
+fn operator_has_key(v: ValueList, names: &NamePool) -> ValueResult {
     let map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
+        hashmap
     } else {
         return type_err!("map", v[0]);
     };
 
-    if match &v[1] {
-        Value::Keyword(s) | Value::Sym(s) => map.contains_key(s),
-        x => return type_err!("keyword", x.clone())
+    if match MapKey::from_value(&v[1], names) {
+        Some(key) => map.contains_key(&key),
+        None => return type_err!("keyword", v[1].clone())
     } {
         return Ok(Value::True);
     };
     Ok(Value::False)
 }
 
-fn core_map_keys(v: ValueList, _names: &NamePool) -> ValueResult {
+// Keyword keys come back as keywords without touching their names, only
+// string keys need a string built for them
+fn core_map_keys(v: ValueList, names: &NamePool) -> ValueResult {
     let map = if let Value::Map(hashmap) = &v[0] {
-        (**hashmap).clone()
+        hashmap
     } else {
         return type_err!("map", v[0]);
     };
 
-    let mut keys: ValueList = vec![];
-    for (k, _) in map {
-        keys.push(Value::Keyword(k))
-    }
+    let keys: ValueList = map.keys().map(|k| k.to_value(names)).collect();
     Ok(keys.into())
 }
 
+// map-keys, map-vals and map-entries walk the same map in the same order,
+// so the nth key always belongs with the nth value
+fn core_map_vals(v: ValueList, _names: &NamePool) -> ValueResult {
+    let map = if let Value::Map(hashmap) = &v[0] {
+        hashmap
+    } else {
+        return type_err!("map", v[0]);
+    };
+
+    let vals: ValueList = map.values().cloned().collect();
+    Ok(vals.into())
+}
+
+fn core_map_entries(v: ValueList, names: &NamePool) -> ValueResult {
+    let map = if let Value::Map(hashmap) = &v[0] {
+        hashmap
+    } else {
+        return type_err!("map", v[0]);
+    };
+
+    let entries: ValueList = map.iter()
+        .map(|(k, v)| vec![k.to_value(names), v.clone()].into())
+        .collect();
+    Ok(entries.into())
+}
+
+// Keys are passed like map-keys gives them, in the unspecified order of the
+// underlying hash map
+fn core_reduce_kv(v: ValueList, names: &NamePool) -> ValueResult {
+    let func = &v[0];
+    let map = match &v[2] {
+        Value::Map(hashmap) => hashmap,
+        Value::Nil => return Ok(v[1].clone()),
+        x => return type_err!("map", x)
+    };
+
+    let mut acc = v[1].clone();
+    for (k, val) in map.iter() {
+        acc = func.apply(vec![acc, k.to_value(names), val.clone()], names)?;
+    }
+    Ok(acc)
+}
+
 fn pred_atom(v: ValueList, _names: &NamePool) -> ValueResult {
     predicate_op! {v;
         Value::List(l) => Ok((l.len() == 0).into());
@@ -339,6 +505,36 @@ fn pred_list(v: ValueList, _names: &NamePool) -> ValueResult {
     }
 }
 
+fn pred_lazy(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::Lazy{..} => Ok(Value::True);
+        Ok(Value::False)
+    }
+}
+
+fn pred_string_builder(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::StringBuilder(_) => Ok(Value::True);
+        Ok(Value::False)
+    }
+}
+
+fn pred_vector(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::PVector(_) => Ok(Value::True);
+        Ok(Value::False)
+    }
+}
+
+// The head of a lazy sequence is computed when it is built, so this tells if
+// the rest has been computed yet. Every other value is already realized
+fn pred_realized(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::Lazy{data, ..} => Ok(data.is_realized().into());
+        Ok(Value::True)
+    }
+}
+
 fn pred_hashmap(v: ValueList, _names: &NamePool) -> ValueResult {
     predicate_op! {v;
         Value::Map(_) => Ok(Value::True);
@@ -355,8 +551,19 @@ fn pred_nil(v: ValueList, _names: &NamePool) -> ValueResult {
 }
 
 fn pred_number(v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(numeric::is_number(&v[0]).into())
+}
+
+fn pred_complex(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::Complex(..) => Ok(Value::True);
+        Ok(Value::False)
+    }
+}
+
+fn pred_rational(v: ValueList, _names: &NamePool) -> ValueResult {
     predicate_op! {v;
-        Value::Num(_) => Ok(Value::True);
+        Value::Rational(..) => Ok(Value::True);
         Ok(Value::False)
     }
 }
@@ -405,20 +612,42 @@ fn core_apply(v: ValueList, names: &NamePool) -> ValueResult {
     }
 }
 
+// Lazy sequences, given or returned by the function, are realized
 fn core_flatmap(v: ValueList, names: &NamePool) -> ValueResult {
     let func = &v[0];
-    if let Value::List(seq) = &v[1] {
-        let mut result: Vec<Value> = vec![];
-        for expr in seq.into_iter(){
-            match func.apply(vec![expr.clone()], names)? {
-                Value::List(ls) => result.extend(ls.into_iter().cloned()),
-                Value::Nil => {}
-                x => return type_err!("list", x.clone())
-            }
+    let seq = match core_collect(vec![v[1].clone()], names)? {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let mut result: Vec<Value> = vec![];
+    for expr in seq.iter(){
+        match core_collect(vec![func.apply(vec![expr.clone()], names)?], names)? {
+            Value::List(ls) => result.extend(ls.iter().cloned()),
+            Value::Nil => {}
+            x => return type_err!("list", x.clone())
         }
-        return Ok(result.into())
-    }else{
-        return type_err!("list", v[1])
+    }
+    Ok(result.into())
+}
+
+fn core_vector(v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(Value::PVector(Rc::new(v.into_iter().collect())))
+}
+
+fn core_vec(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::PVector(_) => Ok(v[0].clone()),
+        Value::Str(s) => Ok(Value::PVector(Rc::new(s.inner().chars().map(Value::Char).collect()))),
+        Value::List(_) | Value::Nil | Value::Lazy{..} => Ok(Value::PVector(Rc::new(helper_realize(&v[0], usize::MAX)?.into_iter().collect()))),
+        x => type_err!("sequence", x)
+    }
+}
+
+fn core_push(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::PVector(vector) => Ok(Value::PVector(Rc::new(vector.push(v[1].clone())))),
+        x => type_err!("vector", x)
     }
 }
 
@@ -432,29 +661,68 @@ fn core_map(v: ValueList, names: &NamePool) -> ValueResult {
             }
             return Ok(result.into())
         },
+        Value::Nil => Ok(Value::Nil),
+        Value::PVector(vector) => {
+            let result = vector.iter().map(|expr| func.apply(vec![expr.clone()], names)).collect::<Result<PVector, error::Error>>()?;
+            Ok(Value::PVector(Rc::new(result)))
+        },
         Value::Lazy{
             data, env, eval
-        } => Ok(Value::Lazy {
-            eval: *eval, env: env.clone(), 
-            data: Rc::new(LazyData {
-                head: func.apply(vec![data.head.clone()], &data.names)?,
-                tail: vater!{ (MAP [func.clone()] [data.tail.clone()]) },
-                names: data.names.clone()
+        } => {
+            let map = types::func(crate::names::builtin::MAP, Arity::Exact(2), helper_map_rest);
+            Ok(Value::Lazy {
+                eval: *eval, env: env.clone(),
+                data: LazyData::new(
+                    func.apply(vec![data.head.clone()], &data.names)?,
+                    vater!{ ([map] [func.clone()] [v[1].clone()]) },
+                    data.names.clone()
+                )
             })
-        }),
+        },
         x => return type_err!("sequence", x.clone())
     }
 }
 
+// Tail of a lazy map, maps over the rest of the source sequence
+fn helper_map_rest(v: ValueList, names: &NamePool) -> ValueResult {
+    core_map(vec![v[0].clone(), v[1].rest()?], names)
+}
+
 fn core_filter(v: ValueList, names: &NamePool) -> ValueResult {
+    helper_filter(&v[0], v[1].clone(), true, names)
+}
+
+fn core_remove(v: ValueList, names: &NamePool) -> ValueResult {
+    helper_filter(&v[0], v[1].clone(), false, names)
+}
+
+/// Splits a list in one pass into the elements that pass the predicate and the ones that don't
+fn core_separate(v: ValueList, names: &NamePool) -> ValueResult {
     let func = &v[0];
-    let mut seq = v[1].clone();
+    let seq = match &v[1] {
+        Value::List(seq) => seq.clone(),
+        Value::Nil => return Ok(vec![Value::Nil, Value::Nil].into()),
+        x => return type_err!("list", x)
+    };
+    let (mut matching, mut rest): (ValueList, ValueList) = (vec![], vec![]);
+    for expr in seq.iter() {
+        if func.apply(vec![expr.clone()], names)?.is_false() {
+            rest.push(expr.clone())
+        } else {
+            matching.push(expr.clone())
+        }
+    }
+    Ok(vec![matching.into(), rest.into()].into())
+}
+
+/// Keeps the elements for which `func` is truthy, or falsy when `keep` is false
+fn helper_filter(func: &Value, mut seq: Value, keep: bool, names: &NamePool) -> ValueResult {
     loop {
         match seq {
             Value::List(seq) => {
                 let mut result: Vec<Value> = Vec::new();
                 for expr in seq.into_iter(){
-                    if !func.apply(vec![expr.clone()], names)?.is_false() {
+                    if func.apply(vec![expr.clone()], names)?.is_false() != keep {
                         result.push(expr.clone())
                     }
                 }
@@ -463,16 +731,22 @@ fn core_filter(v: ValueList, names: &NamePool) -> ValueResult {
             Value::Lazy{
                 data, env, eval
             } => {
-                if func.apply(vec![data.head.clone()], names)?.is_false() {
-                    seq = eval(data.tail.clone(), env.clone(), data.names.clone())?
+                if func.apply(vec![data.head.clone()], names)?.is_false() == keep {
+                    seq = data.force_tail(&env, eval)?
                 } else {
+                    let filter = if keep {
+                        types::func(crate::names::builtin::FILTER, Arity::Exact(2), helper_filter_rest)
+                    } else {
+                        types::func(crate::names::builtin::REMOVE, Arity::Exact(2), helper_remove_rest)
+                    };
+                    let source = Value::Lazy { eval, env: env.clone(), data: data.clone() };
                     return Ok(Value::Lazy {
                         eval, env: env.clone(),
-                        data: Rc::new(LazyData {
-                            head: data.head.clone(),
-                            tail: vater!{ (FILTER [func.clone()] [data.tail.clone()]) },
-                            names: data.names.clone()
-                        })
+                        data: LazyData::new(
+                            data.head.clone(),
+                            vater!{ ([filter] [func.clone()] [source]) },
+                            data.names.clone()
+                        )
                     })
                 }
             },
@@ -482,188 +756,1371 @@ fn core_filter(v: ValueList, names: &NamePool) -> ValueResult {
     }
 }
 
-fn core_append(v: ValueList, _names: &NamePool) -> ValueResult {
-    let mut result: Vec<Value> = vec![];
-    for seq in v {
-        match seq {
-            Value::List(l) => {
-                result.extend_from_slice(l.inner());
-            }
-            Value::Nil => {}
-            x => return type_err!("sequence", x.clone())
-        }
-    }
-    Ok(result.into())
+// Tail of a lazy filter, filters the rest of the source sequence
+fn helper_filter_rest(v: ValueList, names: &NamePool) -> ValueResult {
+    core_filter(vec![v[0].clone(), v[1].rest()?], names)
 }
 
-fn core_time_ms(_v: ValueList, _names: &NamePool) -> ValueResult {
-    Ok(Value::Num(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as f64))
+fn helper_remove_rest(v: ValueList, names: &NamePool) -> ValueResult {
+    core_remove(vec![v[0].clone(), v[1].rest()?], names)
 }
 
-fn helper_print_lazy(val: &Value, names: &NamePool) -> ValueResult {
-    match val {
-        Value::Lazy{env, eval, data} => {                
-            let mut nth = data.tail.clone();
-            let mut env = env.clone();
-            print!("({}", Printer::str_name(&data.head, names));
-            loop {
-                match eval(nth, env.clone(), data.names.clone())? {
-                    Value::Lazy{env: tenv, data, ..} => {
-                        print!(" {}", Printer::str_name(&data.head, &data.names));
-                        nth = data.tail.clone();
-                        env = tenv;
-                    }
-                    Value::Nil => {
-                        print!(")");
-                        return Ok(Value::Nil)
-                    }
-                    x => {
-                        print!(" {})", Printer::str_name(&x, &data.names));
-                        return Ok(Value::Nil)
-                    }
-                }
-            }
+/// Finds the value whose key is preferred by `better`, on ties the first value wins
+fn helper_extreme_key(v: &ValueList, names: &NamePool, better: fn(Ordering) -> bool) -> ValueResult {
+    let func = &v[0];
+    let mut best: Option<(Value, &Value)> = None;
+    for expr in v[1..].iter() {
+        let key = func.apply(vec![expr.clone()], names)?;
+        if !numeric::is_real(&key) {
+            return type_err!("real number", key)
+        }
+        match &best {
+            Some((best_key, _)) if !numeric::compare(&key, best_key).is_some_and(better) => {},
+            _ => best = Some((key, expr))
         }
-        val => { print!("{}", Printer::str_name(val, names)); Ok(Value::Nil)}
     }
+    Ok(best.map(|(_, val)| val.clone()).into())
 }
 
-fn core_println(v: ValueList, names: &NamePool) -> ValueResult {
-    let mut it = v.iter();
-    if let Some(val) = it.next() {
-        helper_print_lazy(val, names)?;
-    }
-    for expr in it {
-        print!(" ");
-        helper_print_lazy(expr, names)?;
-    }
-    println!();
-    Ok(Value::Nil)
+fn core_max_key(v: ValueList, names: &NamePool) -> ValueResult {
+    helper_extreme_key(&v, names, Ordering::is_gt)
 }
 
-fn core_input(_v: ValueList, _names: &NamePool) -> ValueResult {
-    let mut input = String::new();
-    match std::io::stdin().read_line(&mut input) {
-        Ok(_) => Ok(Value::Str(input.into())),
-        Err(err) => Err(format!("IoError: {}", err).into())
-    }
+fn core_min_key(v: ValueList, names: &NamePool) -> ValueResult {
+    helper_extreme_key(&v, names, Ordering::is_lt)
 }
 
-fn core_print(v: ValueList, names: &NamePool) -> ValueResult {
-    let mut it = v.iter();
-    if let Some(val) = it.next() {
-        helper_print_lazy(val, names)?;
+/// Finds the element preferred by `better` in a list of numbers, strings or chars
+fn helper_extreme(list: &Value, better: fn(std::cmp::Ordering) -> bool) -> ValueResult {
+    let list = match list {
+        Value::List(l) if !l.is_empty() => l,
+        Value::List(_) | Value::Nil => return Err("An empty list has no maximum or minimum".into()),
+        x => return type_err!("list", x)
+    };
+    let mut best = &list[0];
+    for expr in list.iter().skip(1) {
+        let order = match (expr, best) {
+            (a, b) if numeric::is_real(a) && numeric::is_real(b) => numeric::compare(a, b),
+            (Value::Str(a), Value::Str(b)) => Some(a.inner().cmp(b.inner())),
+            (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+            (Value::Str(_), _) | (Value::Char(_), _) => return type_err!("list of the same type", Value::List(list.clone())),
+            (x, _) if numeric::is_real(x) => return type_err!("list of the same type", Value::List(list.clone())),
+            (x, _) => return type_err!("number", x)
+        };
+        if order.is_some_and(better) {
+            best = expr;
+        }
     }
-    for expr in it {
-        print!(" ");
-        helper_print_lazy(expr, names)?;
+    match best {
+        Value::Str(_) | Value::Char(_) => Ok(best.clone()),
+        x if numeric::is_real(x) => Ok(best.clone()),
+        x => type_err!("number", x)
     }
-    Ok(Value::Nil)
 }
 
-fn core_repr(v: ValueList, names: &NamePool) -> ValueResult {
-    Ok(Value::Str(format!("{}", Printer::repr_name(&v[0], names)).into()))
+fn core_maximum(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_extreme(&v[0], std::cmp::Ordering::is_gt)
 }
 
-fn operator_len(v: ValueList, _names: &NamePool) -> ValueResult {
-    let mut len = 0;
-    let mut val = v[0].clone();
+fn core_minimum(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_extreme(&v[0], std::cmp::Ordering::is_lt)
+}
+
+/// Folds a list or lazy sequence of numbers without collecting it first
+fn helper_fold_numbers(seq: &Value, init: f64, op: Op) -> ValueResult {
+    let mut acc = Value::Num(init);
+    let step = |acc: &Value, x: &Value| match numeric::arith(op, acc, x) {
+        Some(n) => Ok(n),
+        None => type_err!("number", x),
+    };
+    let mut seq = seq.clone();
     loop {
-        match val {
-            Value::List(l) => return Ok(Value::Num(l.len() as f64)),
-            Value::Str(s) => return Ok(Value::Num(s.len() as f64)),
+        match seq {
+            Value::List(l) => {
+                for expr in l.iter() {
+                    acc = step(&acc, expr)?;
+                }
+                break
+            }
             Value::Lazy{data, env, eval} => {
-                len += 1;
-                val = eval(data.tail.clone(), env.clone(), data.names.clone())?;
+                acc = step(&acc, &data.head)?;
+                seq = data.force_tail(&env, eval)?;
             }
             Value::Nil => break,
-            x => return type_err!("sequence", x.clone()),
+            x => return type_err!("list", x)
         }
     }
-    Ok(Value::Num(len as f64))
+    Ok(acc)
 }
 
-fn core_read(v: ValueList, names: &NamePool) -> ValueResult {
-    if let Value::Str(input) = v[0].clone(){
-        let input = input.inner().to_owned();
-        let mut tk = parser::Reader::new(&input, names);
-        if let Ok(tok) = tk.next_token() {
-            match tk.parse_expr(tok) {
-                parser::ParserResult::Expr(expr) => Ok(expr),
-                parser::ParserResult::TokenErr(err) => Err(err.into()),
-                parser::ParserResult::EofErr => Err(format!("Error: Unexpected EOF").into()),
+fn core_sum(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_fold_numbers(&v[0], 0f64, Op::Add)
+}
+
+fn core_product(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_fold_numbers(&v[0], 1f64, Op::Mul)
+}
+
+// Only nil results are dropped, a #f returned by the function is kept
+fn core_keep(v: ValueList, names: &NamePool) -> ValueResult {
+    let func = &v[0];
+    match &v[1] {
+        Value::List(seq) => {
+            let mut result: Vec<Value> = Vec::new();
+            for expr in seq.iter() {
+                let res = func.apply(vec![expr.clone()], names)?;
+                if !res.is_nil() {
+                    result.push(res)
+                }
             }
-        }else{
-            Err("Invalid Syntax".into())
-        }
-    }else{
-        type_err!("string", v[0])
+            Ok(result.into())
+        },
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("list", x)
     }
 }
 
-fn core_read_file(v: ValueList, _names: &NamePool) -> ValueResult {
-    let file = File::open(match &v[0] {
-        Value::Str(s) => s.inner().to_owned(),
-        _ => return type_err!("string", v[0])
-    });
-    let mut contents = String::new();
-    if let Ok(mut file) = file {
-        match file.read_to_string(&mut contents) {
-            Ok(_) => Ok(Value::Str(contents.into())),
-            Err(err) => Err(format!("Couldn't read file: {:?}", err).into())
+// A new partition starts each time the function gives a result different from
+// the one before it, so (partition-by id [1 1 2 1]) is ((1 1) (2) (1))
+fn core_partition_by(v: ValueList, names: &NamePool) -> ValueResult {
+    let func = &v[0];
+    let seq = match &v[1] {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let mut result: ValueList = vec![];
+    let mut current: ValueList = vec![];
+    let mut last: Option<Value> = None;
+    for expr in seq.iter() {
+        let key = func.apply(vec![expr.clone()], names)?;
+        if last.as_ref().is_some_and(|last| *last != key) {
+            result.push(std::mem::take(&mut current).into());
         }
-        
-    } else {
-        Err("Couldn't open file".into())
+        current.push(expr.clone());
+        last = Some(key);
     }
+    if !current.is_empty() {
+        result.push(current.into());
+    }
+    Ok(result.into())
 }
 
-fn operator_inc(v: ValueList, _names: &NamePool) -> ValueResult {
+fn core_list_index_of(v: ValueList, _names: &NamePool) -> ValueResult {
     match &v[0] {
-        Value::Num(n) => Ok(Value::Num(*n + 1f64)),
-        x => type_err!("number", x),
+        Value::List(seq) => Ok(seq.iter().position(|x| *x == v[1]).map(|i| Value::Num(i as f64)).unwrap_or(Value::Nil)),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("list", x)
     }
 }
 
-fn operator_dec(v: ValueList, _names: &NamePool) -> ValueResult {
+fn core_list_last_index_of(v: ValueList, _names: &NamePool) -> ValueResult {
     match &v[0] {
-        Value::Num(n) => Ok(Value::Num(*n - 1f64)),
-        x => type_err!("number", x),
+        Value::List(seq) => Ok(seq.iter().rposition(|x| *x == v[1]).map(|i| Value::Num(i as f64)).unwrap_or(Value::Nil)),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("list", x)
     }
 }
 
-fn core_collect(v: ValueList, _names: &NamePool) -> ValueResult {
-    match &v[0] {
-        Value::List(_) => Ok(v[0].clone()),
-        Value::Nil => Ok(Value::Nil),
-        Value::Lazy{env, eval, data} => {
-            let mut collect: ValueList = vec![data.head.clone()];
-            
-            let mut nth = data.tail.clone();
-            let mut env = env.clone();
+/// `(value count)` pairs from the most frequent value down, keeping the n first
+/// if n is given. Values with the same count stay in the order they first appear
+fn core_most_common(v: ValueList, _names: &NamePool) -> ValueResult {
+    let seq = match &v[0] {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let limit = match v.get(1) {
+        Some(n) => helper_index(n)?,
+        None => usize::MAX,
+    };
+    let mut counts: Vec<(&Value, usize)> = vec![];
+    for expr in seq.iter() {
+        match counts.iter_mut().find(|(value, _)| *value == expr) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((expr, 1)),
+        }
+    }
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    Ok(counts.into_iter()
+        .take(limit)
+        .map(|(value, count)| vec![value.clone(), Value::Num(count as f64)].into())
+        .collect::<ValueList>()
+        .into())
+}
+
+// Only drops an element equal to the one right before it, so unlike a distinct
+// the repeats that aren't next to each other stay: (dedupe [1 1 2 1]) is (1 2 1)
+fn core_dedupe(v: ValueList, _names: &NamePool) -> ValueResult {
+    let seq = match &v[0] {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let mut result: ValueList = Vec::with_capacity(seq.len());
+    for expr in seq.iter() {
+        if result.last() != Some(expr) {
+            result.push(expr.clone());
+        }
+    }
+    Ok(result.into())
+}
+
+// Turns each run of equal elements into a (count value) pair
+fn core_rle_encode(v: ValueList, _names: &NamePool) -> ValueResult {
+    let seq = match &v[0] {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let mut runs: Vec<(usize, &Value)> = vec![];
+    for expr in seq.iter() {
+        match runs.last_mut() {
+            Some((count, value)) if *value == expr => *count += 1,
+            _ => runs.push((1, expr)),
+        }
+    }
+    Ok(runs.into_iter()
+        .map(|(count, value)| vec![Value::Num(count as f64), value.clone()].into())
+        .collect::<ValueList>()
+        .into())
+}
+
+// Longest list rle-decode will build, so a bad count fails instead of exhausting memory
+const MAX_RLE_DECODED: usize = 1 << 24;
+
+fn core_rle_decode(v: ValueList, _names: &NamePool) -> ValueResult {
+    let seq = match &v[0] {
+        Value::List(seq) => seq,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("list", x)
+    };
+    let mut result: ValueList = vec![];
+    for pair in seq.iter() {
+        match pair {
+            Value::List(l) if l.len() == 2 => {
+                let count = helper_index(&l[0])?;
+                if count > MAX_RLE_DECODED - result.len() {
+                    return Err(format!("rle-decode would build more than {} elements", MAX_RLE_DECODED).into())
+                }
+                result.extend(std::iter::repeat_n(l[1].clone(), count));
+            }
+            x => return type_err!("(count value) pair", x)
+        }
+    }
+    Ok(result.into())
+}
+
+fn core_reductions(v: ValueList, names: &NamePool) -> ValueResult {
+    let func = &v[0];
+    let (init, seq) = n_args! { v;
+        2 => (None, &v[1]),
+        _ => (Some(v[1].clone()), &v[2])
+    };
+    let seq = match seq {
+        Value::List(l) => l.inner(),
+        Value::Nil => &[],
+        x => return type_err!("list", x)
+    };
+
+    let (mut acc, seq) = match (init, seq.split_first()) {
+        (Some(init), _) => (init, seq),
+        (None, Some((first, rest))) => (first.clone(), rest),
+        (None, None) => return Ok(vec![func.apply(vec![], names)?].into()),
+    };
+    let mut result: ValueList = Vec::with_capacity(seq.len() + 1);
+    result.push(acc.clone());
+    for expr in seq {
+        acc = func.apply(vec![acc, expr.clone()], names)?;
+        result.push(acc.clone());
+    }
+    Ok(result.into())
+}
+
+// Lazy sequences are realized, so they must be finite
+fn core_append(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut result: Vec<Value> = vec![];
+    for seq in v {
+        let seq = match seq {
+            Value::Lazy{..} => core_collect(vec![seq], names)?,
+            seq => seq
+        };
+        match seq {
+            Value::List(l) => {
+                result.extend_from_slice(l.inner());
+            }
+            Value::Nil => {}
+            x => return type_err!("sequence", x.clone())
+        }
+    }
+    Ok(result.into())
+}
+
+/// Wall clock milliseconds since the epoch. The system clock can be moved, even
+/// backwards, so durations should be measured with `mono-ms`
+fn core_time_ms(_v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(Value::Num(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as f64))
+}
+
+// Dates are all UTC, converted with the days-from-civil algorithms of
+// http://howardhinnant.github.io/date_algorithms.html
+
+/// (year, month, day) of a count of days since 1970-01-01
+fn helper_civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if month <= 2 { 1 } else { 0 }, month, day)
+}
+
+fn helper_days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn helper_days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Splits milliseconds since the epoch into year, month, day, hour, minute, second and millisecond
+fn helper_split_time(ms: i64) -> [i64; 7] {
+    let (days, ms_of_day) = (ms.div_euclid(86_400_000), ms.rem_euclid(86_400_000));
+    let (year, month, day) = helper_civil_from_days(days);
+    let secs = ms_of_day / 1000;
+    [year, month, day, secs / 3600, secs / 60 % 60, secs % 60, ms_of_day % 1000]
+}
+
+/// The current UTC time as a map of :year :month :day :hour :minute :second and :millisecond
+fn core_now(_v: ValueList, names: &NamePool) -> ValueResult {
+    let ms = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as i64;
+    let keys = ["year", "month", "day", "hour", "minute", "second", "millisecond"];
+    Ok(Value::Map(keys.iter()
+        .zip(helper_split_time(ms).iter())
+        .map(|(key, n)| (MapKey::Keyword(names.add(key)), Value::Num(*n as f64)))
+        .collect()))
+}
+
+/// Milliseconds since the epoch as an ISO 8601 UTC string like 2024-01-31T08:05:09.250Z
+fn core_format_time(v: ValueList, _names: &NamePool) -> ValueResult {
+    let ms = match &v[0] {
+        Value::Num(n) if n.is_finite() => n.floor() as i64,
+        x => return type_err!("number", x),
+    };
+    let [year, month, day, hour, minute, second, millis] = helper_split_time(ms);
+    Ok(Value::Str(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, hour, minute, second, millis).into()))
+}
+
+/// Reads `YYYY-MM-DD` optionally followed by `THH:MM:SS`, a fraction of a second and
+/// `Z` or a `+HH:MM`/`-HH:MM` offset, giving milliseconds since the epoch
+fn core_parse_time(v: ValueList, _names: &NamePool) -> ValueResult {
+    let text = match &v[0] {
+        Value::Str(s) => s.inner(),
+        x => return type_err!("string", x),
+    };
+    let invalid = || -> error::Error { format!("Invalid ISO 8601 time {:?}", text).into() };
+    let field = |range: std::ops::Range<usize>| -> Result<i64, error::Error> {
+        let digits = text.get(range).ok_or_else(invalid)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid())
+        }
+        digits.parse::<i64>().map_err(|_| invalid())
+    };
+    let separators = |positions: &[(usize, u8)]| positions.iter().all(|(i, b)| text.as_bytes().get(*i) == Some(b));
+    if !separators(&[(4, b'-'), (7, b'-')]) {
+        return Err(invalid())
+    }
+    let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+    if !(1..=12).contains(&month) || !(1..=helper_days_in_month(year, month)).contains(&day) {
+        return Err(invalid())
+    }
+    let mut ms = helper_days_from_civil(year, month, day) * 86_400_000;
+    let mut rest = &text[10..];
+    if !rest.is_empty() {
+        if !(rest.starts_with('T') || rest.starts_with(' ')) || !separators(&[(13, b':'), (16, b':')]) {
+            return Err(invalid())
+        }
+        let (hour, minute, second) = (field(11..13)?, field(14..16)?, field(17..19)?);
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(invalid())
+        }
+        ms += (hour * 3600 + minute * 60 + second) * 1000;
+        rest = &text[19..];
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let digits = fraction.bytes().take_while(|b| b.is_ascii_digit()).count();
+            if digits == 0 {
+                return Err(invalid())
+            }
+            let millis = format!("{:0<3}", &fraction[..digits.min(3)]);
+            ms += millis.parse::<i64>().map_err(|_| invalid())?;
+            rest = &fraction[digits..];
+        }
+        match rest.as_bytes().first() {
+            Some(b'Z') if rest.len() == 1 => {}
+            Some(sign @ (b'+' | b'-')) if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+                let offset = rest[1..3].parse::<i64>().map_err(|_| invalid())? * 60 + rest[4..6].parse::<i64>().map_err(|_| invalid())?;
+                ms -= if *sign == b'+' { offset } else { -offset } * 60_000;
+            }
+            None => {}
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(Value::Num(ms as f64))
+}
+
+/// The environment variables as they are when this is called, names and values
+/// that aren't valid unicode are left out
+fn core_env_vars(_v: ValueList, names: &NamePool) -> ValueResult {
+    Ok(Value::Map(std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .map(|(key, value)| (MapKey::Str(names.add(&key)), Value::Str(value.into())))
+        .collect()))
+}
+
+/// Runs a program to completion, `(run-command prog args.. [:stdin input])`.
+/// Gives back a map of its `:stdout`, `:stderr` and `:exit-code`, which is nil
+/// when the program was killed by a signal
+fn core_run_command(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut strings = vec![];
+    let mut input = None;
+    let mut it = v.iter();
+    while let Some(arg) = it.next() {
+        match arg {
+            Value::Str(s) => strings.push(s.inner().to_owned()),
+            Value::Keyword(k) if names.get(*k) == "stdin" => match it.next() {
+                Some(Value::Str(s)) => input = Some(s.inner().to_owned()),
+                Some(x) => return type_err!("string", x),
+                None => return Err("run-command: missing input after :stdin".into()),
+            },
+            x => return type_err!("string", x),
+        }
+    }
+    if strings.is_empty() {
+        return Err("run-command: missing the program to run".into())
+    }
+    let mut command = std::process::Command::new(&strings[0]);
+    command.args(&strings[1..])
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command.spawn().map_err(|err| format!("IoError: couldn't run {}: {}", strings[0], err))?;
+    // written from another thread so a child filling its output pipe can't block us
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => Some(std::thread::spawn(move || stdin.write_all(input.as_bytes()))),
+        _ => None,
+    };
+    let output = child.wait_with_output().map_err(|err| format!("IoError: {}", err))?;
+    if let Some(writer) = writer {
+        // the program may exit without reading everything, that isn't an error
+        let _ = writer.join();
+    }
+    let mut map = PMap::new();
+    map.insert(MapKey::Keyword(names.add("stdout")), Value::Str(String::from_utf8_lossy(&output.stdout).into_owned().into()));
+    map.insert(MapKey::Keyword(names.add("stderr")), Value::Str(String::from_utf8_lossy(&output.stderr).into_owned().into()));
+    map.insert(MapKey::Keyword(names.add("exit-code")), output.status.code().map_or(Value::Nil, |code| Value::Num(code as f64)));
+    Ok(Value::Map(map))
+}
+
+thread_local! {
+    /// Start of the monotonic clock read by `mono-ms`
+    static MONO_START: Instant = Instant::now();
+}
+
+/// Milliseconds, with a fraction, from a clock that never goes backwards. Only
+/// the difference between two readings means anything
+fn core_mono_ms(_v: ValueList, _names: &NamePool) -> ValueResult {
+    Ok(Value::Num(MONO_START.with(|start| start.elapsed().as_secs_f64() * 1000.0)))
+}
+
+thread_local! {
+    /// State of the random generator, seeded from the clock on first use unless `rand-seed` set it
+    static RNG_STATE: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Next 64 random bits, from a splitmix64 generator. Not for cryptography
+fn helper_random() -> u64 {
+    RNG_STATE.with(|state| {
+        let seed = state.get().unwrap_or_else(|| SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos() as u64);
+        let next = seed.wrapping_add(0x9E3779B97F4A7C15);
+        state.set(Some(next));
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    })
+}
+
+/// `(rand)` is a float in [0, 1), `(rand n)` an integer in [0, n)
+fn core_rand(v: ValueList, _names: &NamePool) -> ValueResult {
+    let unit = (helper_random() >> 11) as f64 / (1u64 << 53) as f64;
+    match v.first() {
+        None => Ok(Value::Num(unit)),
+        Some(Value::Num(n)) if n.fract() == 0.0 && *n > 0.0 => Ok(Value::Num((unit * n).floor())),
+        Some(x) => type_err!("positive integer", x),
+    }
+}
+
+/// Makes the following random values the same on every run
+fn core_rand_seed(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Num(n) if n.fract() == 0.0 => RNG_STATE.with(|state| state.set(Some(*n as i64 as u64))),
+        x => return type_err!("integer", x),
+    }
+    Ok(Value::Nil)
+}
+
+/// Random version 4 UUID, drawn from the same generator as `rand`
+fn core_uuid(_v: ValueList, _names: &NamePool) -> ValueResult {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&helper_random().to_be_bytes());
+    bytes[8..].copy_from_slice(&helper_random().to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    Ok(Value::Str(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]).into()))
+}
+
+thread_local! {
+    /// Where `print` and `println` write to, stdout when not set
+    static OUTPUT: RefCell<Option<Box<dyn Write>>> = const { RefCell::new(None) };
+    /// Where `input` and the other readers read from, stdin when not set
+    static INPUT: RefCell<Option<Box<dyn BufRead>>> = const { RefCell::new(None) };
+}
+
+/// Sends the printed output somewhere else, `None` goes back to stdout.
+/// Gives back the writer that was in use before
+pub fn set_output(out: Option<Box<dyn Write>>) -> Option<Box<dyn Write>> {
+    OUTPUT.with(|output| output.replace(out))
+}
+
+/// Reads input from somewhere else, `None` goes back to stdin.
+/// Gives back the reader that was in use before
+pub fn set_input(input: Option<Box<dyn BufRead>>) -> Option<Box<dyn BufRead>> {
+    INPUT.with(|current| current.replace(input))
+}
+
+fn write_out(text: &str) -> Result<(), error::Error> {
+    let res = OUTPUT.with(|output| match output.borrow_mut().as_mut() {
+        Some(out) => out.write_all(text.as_bytes()),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    });
+    res.map_err(|err| format!("IoError: {}", err).into())
+}
+
+fn with_input<T>(f: impl FnOnce(&mut dyn BufRead) -> T) -> T {
+    INPUT.with(|input| match input.borrow_mut().as_mut() {
+        Some(input) => f(input),
+        None => f(&mut std::io::stdin().lock()),
+    })
+}
+
+/// A writer appending to a buffer that is still reachable from outside
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs `body` with the printed output going into a string instead, the
+/// previous writer is back in place afterwards even if `body` failed
+pub fn capture_output<T>(body: impl FnOnce() -> T) -> (T, String) {
+    let buffer = Rc::new(RefCell::new(vec![]));
+    let previous = set_output(Some(Box::new(SharedBuffer(buffer.clone()))));
+    let res = body();
+    set_output(previous);
+    let out = String::from_utf8_lossy(&buffer.borrow()).into_owned();
+    (res, out)
+}
+
+/// Runs `body` reading its input from `text` instead, the previous reader
+/// is back in place afterwards even if `body` failed
+pub fn provide_input<T>(text: String, body: impl FnOnce() -> T) -> T {
+    let previous = set_input(Some(Box::new(std::io::Cursor::new(text.into_bytes()))));
+    let res = body();
+    set_input(previous);
+    res
+}
+
+fn helper_print_lazy(val: &Value, names: &NamePool) -> ValueResult {
+    match val {
+        Value::Lazy{env, eval, data} => {                
+            let mut nth = data.force_tail(env, *eval)?;
+            write_out(&format!("({}", Printer::str_name(&data.head, names)))?;
             loop {
-                match eval(nth, env.clone(), data.names.clone())? {
-                    Value::Lazy{env: tenv, data, ..} => {
-                        collect.push(data.head.clone());
-                        nth = data.tail.clone();
-                        env = tenv;
+                match nth {
+                    Value::Lazy{env, eval, data} => {
+                        write_out(&format!(" {}", Printer::str_name(&data.head, &data.names)))?;
+                        nth = data.force_tail(&env, eval)?;
                     }
-                    Value::Nil => break Ok(collect.into()),
-                    Value::List(ls) => {
-                        collect.extend(ls.into_iter().cloned());
-                        break Ok(collect.into())
+                    Value::Nil => {
+                        write_out(")")?;
+                        return Ok(Value::Nil)
                     }
                     x => {
-                        collect.push(x);
-                        break Ok(collect.into())
+                        write_out(&format!(" {})", Printer::str_name(&x, &data.names)))?;
+                        return Ok(Value::Nil)
                     }
                 }
             }
         }
+        val => { write_out(&Printer::str_name(val, names))?; Ok(Value::Nil)}
+    }
+}
+
+/// Returns the first argument so it can be dropped into a pipeline
+fn core_println(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut it = v.iter();
+    if let Some(val) = it.next() {
+        helper_print_lazy(val, names)?;
+    }
+    for expr in it {
+        write_out(" ")?;
+        helper_print_lazy(expr, names)?;
+    }
+    write_out("\n")?;
+    Ok(v.first().cloned().unwrap_or(Value::Nil))
+}
+
+fn core_input(_v: ValueList, _names: &NamePool) -> ValueResult {
+    let mut input = String::new();
+    match with_input(|stdin| stdin.read_line(&mut input)) {
+        Ok(_) => Ok(Value::Str(input.into())),
+        Err(err) => Err(format!("IoError: {}", err).into())
+    }
+}
+
+fn core_read_all_input(_v: ValueList, _names: &NamePool) -> ValueResult {
+    let mut input = String::new();
+    match with_input(|stdin| stdin.read_to_string(&mut input)) {
+        Ok(_) => Ok(Value::Str(input.into())),
+        Err(err) => Err(format!("IoError: {}", err).into())
+    }
+}
+
+// Reads one UTF-8 encoded char from the input, which is buffered so the
+// bytes of a char are never split between calls
+fn core_read_char(_v: ValueList, _names: &NamePool) -> ValueResult {
+    with_input(helper_read_char)
+}
+
+fn helper_read_char(stdin: &mut dyn BufRead) -> ValueResult {
+    let mut bytes = [0u8; 4];
+    match stdin.read(&mut bytes[..1]) {
+        Ok(0) => return Ok(Value::Nil),
+        Ok(_) => {},
+        Err(err) => return Err(format!("IoError: {}", err).into())
+    }
+    let width = match bytes[0] {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Err("IoError: stream did not contain valid UTF-8".into())
+    };
+    if let Err(err) = stdin.read_exact(&mut bytes[1..width]) {
+        return Err(format!("IoError: {}", err).into())
+    }
+    match std::str::from_utf8(&bytes[..width]) {
+        Ok(s) => Ok(s.chars().next().into()),
+        Err(_) => Err("IoError: stream did not contain valid UTF-8".into())
+    }
+}
+
+fn core_print(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut it = v.iter();
+    if let Some(val) = it.next() {
+        helper_print_lazy(val, names)?;
+    }
+    for expr in it {
+        write_out(" ")?;
+        helper_print_lazy(expr, names)?;
+    }
+    Ok(v.first().cloned().unwrap_or(Value::Nil))
+}
+
+/// Lazy sequences, also those inside lists, realized into lists so they print readably
+fn helper_realize_all(value: &Value) -> ValueResult {
+    match value {
+        Value::Lazy{..} => Ok(helper_realize(value, usize::MAX)?.iter().map(helper_realize_all).collect::<Result<ValueList, _>>()?.into()),
+        Value::List(l) => Ok(l.iter().map(helper_realize_all).collect::<Result<ValueList, _>>()?.into()),
+        x => Ok(x.clone()),
+    }
+}
+
+/// Prints the readable form of the values, lazy sequences are realized first
+fn core_pr(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut res = vec![];
+    for e in v.iter() {
+        res.push(Printer::repr_read(&helper_realize_all(e)?, names));
+    }
+    write_out(&res.join(" "))?;
+    Ok(v.first().cloned().unwrap_or(Value::Nil))
+}
+
+fn core_prn(v: ValueList, names: &NamePool) -> ValueResult {
+    let res = core_pr(v, names)?;
+    write_out("\n")?;
+    Ok(res)
+}
+
+/// Calls `f` on the value for its side effects and returns the value itself
+fn core_tap(v: ValueList, names: &NamePool) -> ValueResult {
+    v[1].apply(vec![v[0].clone()], names)?;
+    Ok(v[0].clone())
+}
+
+fn core_spy(v: ValueList, names: &NamePool) -> ValueResult {
+    eprintln!("{}", Printer::repr_name(&v[0], names));
+    Ok(v[0].clone())
+}
+
+fn core_repr(v: ValueList, names: &NamePool) -> ValueResult {
+    Ok(Value::Str(format!("{}", Printer::repr_name(&v[0], names)).into()))
+}
+
+// For numbers, strings, chars, symbols, keywords, booleans and lists of them
+// (read (pr-str x)) gives back a value equal to x
+// Lazy sequences are realized first, as `pr` and `print` do
+fn core_pr_str(v: ValueList, names: &NamePool) -> ValueResult {
+    let res = v.iter()
+        .map(|e| Ok(Printer::repr_read(&helper_realize_all(e)?, names)))
+        .collect::<Result<Vec<String>, error::Error>>()?;
+    Ok(Value::Str(res.join(" ").into()))
+}
+
+fn core_print_str(v: ValueList, names: &NamePool) -> ValueResult {
+    let res = v.iter()
+        .map(|e| Ok(Printer::str_name(&helper_realize_all(e)?, names)))
+        .collect::<Result<Vec<String>, error::Error>>()?;
+    Ok(Value::Str(res.join(" ").into()))
+}
+
+/// Lazy sequences are forced to the end to be counted, so this is O(n) on them
+/// and never returns for an infinite one
+fn operator_len(v: ValueList, _names: &NamePool) -> ValueResult {
+    let mut len = 0;
+    let mut val = v[0].clone();
+    loop {
+        match val {
+            Value::List(l) => return Ok(Value::Num((len + l.len()) as f64)),
+            Value::Str(s) => return Ok(Value::Num((len + s.len()) as f64)),
+            Value::PVector(vector) => return Ok(Value::Num((len + vector.len()) as f64)),
+            Value::Lazy{data, env, eval} => {
+                len += 1;
+                val = data.force_tail(&env, eval)?;
+            }
+            Value::Nil => break,
+            x => return type_err!("sequence", x.clone()),
+        }
+    }
+    Ok(Value::Num(len as f64))
+}
+
+fn core_read(v: ValueList, names: &NamePool) -> ValueResult {
+    if let Value::Str(input) = v[0].clone(){
+        let input = input.inner().to_owned();
+        let mut tk = parser::Reader::new(&input, names);
+        if let Ok(tok) = tk.next_token() {
+            match tk.parse_expr(tok) {
+                parser::ParserResult::Expr(expr) => Ok(expr),
+                parser::ParserResult::TokenErr(err) => Err(err.into()),
+                parser::ParserResult::EofErr => Err(format!("Error: Unexpected EOF").into()),
+            }
+        }else{
+            Err("Invalid Syntax".into())
+        }
+    }else{
+        type_err!("string", v[0])
+    }
+}
+
+/// Reads the form starting at a byte offset of the string, giving back the form
+/// and the offset right after it, or nil when only whitespace and comments are left
+fn core_read_from(v: ValueList, names: &NamePool) -> ValueResult {
+    let (input, start) = match &v[0] {
+        Value::Str(s) => (s, helper_index(&v[1])?),
+        x => return type_err!("string", x)
+    };
+    let input = match input.inner().get(start..) {
+        Some(rest) => rest,
+        None => return Err(format!("Invalid offset {} into a string of {} bytes", start, input.len()).into())
+    };
+    let mut tk = parser::Reader::new(input, names);
+    let expr = match tk.parse_form() {
+        (false, Ok(_)) => return Ok(Value::Nil),
+        (_, Ok(expr)) => expr,
+        (_, Err(err)) => return Err(err.into()),
+    };
+    let next = start + input.len() - tk.remaining();
+    Ok(vec![expr, Value::Num(next as f64)].into())
+}
+
+/// Turns a parsed form into plain data, `[...]` becomes a list and `#[...]` a map.
+/// Symbols (other than nil) are refused so the result never refers to code
+fn helper_to_data(expr: &Value, names: &NamePool) -> ValueResult {
+    match expr {
+        Value::Num(_) | Value::Rational(..) | Value::Complex(..) | Value::BigInt(_)
+        | Value::Str(_) | Value::Char(_) | Value::Keyword(_)
+        | Value::True | Value::False | Value::Nil => Ok(expr.clone()),
+        Value::Sym(s) if *s == crate::names::builtin::NIL => Ok(Value::Nil),
+        Value::List(l) => match &l[0] {
+            Value::Sym(s) if *s == crate::names::builtin::LIST => {
+                Ok(l[1..].iter().map(|e| helper_to_data(e, names)).collect::<Result<ValueList, error::Error>>()?.into())
+            }
+            Value::Sym(s) if *s == crate::names::builtin::HASH_MAP => {
+                if l.len() % 2 != 1 {
+                    return Err(error::Error::KwArgErr(Some(crate::names::builtin::HASH_MAP)));
+                }
+                let mut map: PMap<MapKey, Value> = PMap::new();
+                for i in (1..l.len()).step_by(2) {
+                    match &l[i] {
+                        Value::Keyword(k) => map.insert(MapKey::Keyword(*k), helper_to_data(&l[i+1], names)?),
+                        Value::Str(k) => map.insert(MapKey::Str(names.add(k.inner())), helper_to_data(&l[i+1], names)?),
+                        x => return type_err!("keyword", x)
+                    };
+                }
+                Ok(Value::Map(map))
+            }
+            _ => Ok(l.iter().map(|e| helper_to_data(e, names)).collect::<Result<ValueList, error::Error>>()?.into())
+        }
+        x => type_err!("data", x)
+    }
+}
+
+// Reads a single form as data, nothing in the input is ever evaluated
+fn core_parse_data(v: ValueList, names: &NamePool) -> ValueResult {
+    let expr = core_read(v, names)?;
+    helper_to_data(&expr, names)
+}
+
+fn core_read_file(v: ValueList, _names: &NamePool) -> ValueResult {
+    let file = File::open(match &v[0] {
+        Value::Str(s) => s.inner().to_owned(),
+        _ => return type_err!("string", v[0])
+    });
+    let mut contents = String::new();
+    if let Ok(mut file) = file {
+        match file.read_to_string(&mut contents) {
+            Ok(_) => Ok(Value::Str(contents.into())),
+            Err(err) => Err(format!("Couldn't read file: {:?}", err).into())
+        }
+        
+    } else {
+        Err("Couldn't open file".into())
+    }
+}
+
+fn core_open(v: ValueList, _names: &NamePool) -> ValueResult {
+    let path = match &v[0] {
+        Value::Str(s) => s.inner().to_owned(),
+        x => return type_err!("string", x)
+    };
+    match File::open(path) {
+        Ok(file) => Ok(Value::File(Rc::new(RefCell::new(Some(BufReader::new(file)))))),
+        Err(err) => Err(format!("Couldn't open file: {}", err).into())
+    }
+}
+
+// Returns the next line without its line ending, or nil at the end of the file
+fn core_read_line(v: ValueList, _names: &NamePool) -> ValueResult {
+    let file = match &v[0] {
+        Value::File(file) => file,
+        x => return type_err!("file", x)
+    };
+    let mut file = file.borrow_mut();
+    let reader = match file.as_mut() {
+        Some(reader) => reader,
+        None => return Err("Couldn't read line: file is closed".into())
+    };
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Value::Str(line.into()))
+        }
+        Err(err) => Err(format!("Couldn't read line: {}", err).into())
+    }
+}
+
+fn core_close(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::File(file) => {
+            file.borrow_mut().take();
+            Ok(Value::Nil)
+        }
+        x => type_err!("file", x)
+    }
+}
+
+fn core_regex(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_regex(&v[0]).map(Value::Regex)
+}
+
+/// Strings are compiled on every call, use `regex` to compile a pattern once
+fn helper_regex(value: &Value) -> Result<Rc<Regex>, error::Error> {
+    match value {
+        Value::Regex(regex) => Ok(regex.clone()),
+        Value::Str(s) => match Regex::new(s.inner()) {
+            Ok(regex) => Ok(Rc::new(regex)),
+            Err(err) => Err(format!("Invalid regex {:?}: {}", s.inner(), err).into())
+        },
+        x => type_err!("regex", x)
+    }
+}
+
+fn helper_regex_input(value: &Value) -> Result<&str, error::Error> {
+    match value {
+        Value::Str(s) => Ok(s.inner()),
+        x => type_err!("string", x)
+    }
+}
+
+fn helper_regex_run(res: Result<Option<Captures>, String>) -> Result<Option<Captures>, error::Error> {
+    res.map_err(|err| format!("Regex error: {}", err).into())
+}
+
+// Without groups a match is just the matched string, otherwise it is a list of
+// the whole match followed by every group, nil for groups that didn't take part
+fn helper_regex_match(regex: &Regex, input: &str, caps: &Captures) -> Value {
+    let group = |cap: &Option<(usize, usize)>| match cap {
+        Some((start, end)) => Value::Str(input[*start..*end].into()),
+        None => Value::Nil,
+    };
+    if regex.groups() == 0 {
+        group(&caps[0])
+    } else {
+        Value::List(caps.iter().map(group).collect::<ValueList>().into())
+    }
+}
+
+fn core_re_find(v: ValueList, _names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    let input = helper_regex_input(&v[1])?;
+    Ok(match helper_regex_run(regex.find_at(input, 0))? {
+        Some(caps) => helper_regex_match(&regex, input, &caps),
+        None => Value::Nil,
+    })
+}
+
+fn core_re_match(v: ValueList, _names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    let input = helper_regex_input(&v[1])?;
+    Ok(match helper_regex_run(regex.match_full(input))? {
+        Some(caps) => helper_regex_match(&regex, input, &caps),
+        None => Value::Nil,
+    })
+}
+
+fn core_re_find_all(v: ValueList, _names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    let input = helper_regex_input(&v[1])?;
+    let mut res = vec![];
+    let mut pos = 0;
+    while pos <= input.len() {
+        match helper_regex_run(regex.find_at(input, pos))? {
+            Some(caps) => {
+                res.push(helper_regex_match(&regex, input, &caps));
+                pos = regex.next_start(input, &caps);
+            }
+            None => break
+        }
+    }
+    Ok(Value::List(res.into()))
+}
+
+// Matches are non-overlapping: after each match the search continues from its end
+// (one char further for empty matches), each match is only searched for when needed
+fn core_re_seq(v: ValueList, names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    helper_regex_input(&v[1])?;
+    helper_re_seq(vec![Value::Regex(regex), v[1].clone(), Value::Num(0f64)], names)
+}
+
+fn helper_re_seq(v: ValueList, names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    let input = helper_regex_input(&v[1])?;
+    let pos = helper_index(&v[2])?;
+    if pos > input.len() {
+        return Ok(Value::Nil)
+    }
+    let caps = match helper_regex_run(regex.find_at(input, pos))? {
+        Some(caps) => caps,
+        None => return Ok(Value::Nil)
+    };
+    let next = types::func(names.add("re-seq-rest"), Arity::Exact(3), helper_re_seq);
+    let pos = Value::Num(regex.next_start(input, &caps) as f64);
+    Ok(Value::Lazy {
+        eval: crate::eval, env: EnvStruct::new(None),
+        data: LazyData::new(
+            helper_regex_match(&regex, input, &caps),
+            vater!{ ([next] [v[0].clone()] [v[1].clone()] [pos]) },
+            Rc::new(names.clone())
+        )
+    })
+}
+
+// Replaces every match, `$0`-`$9` in the replacement insert groups and `$$` a dollar sign
+fn core_re_replace(v: ValueList, _names: &NamePool) -> ValueResult {
+    let regex = helper_regex(&v[0])?;
+    let input = helper_regex_input(&v[1])?;
+    let replacement = helper_regex_input(&v[2])?;
+    let mut res = String::new();
+    let mut copied = 0;
+    let mut pos = 0;
+    while pos <= input.len() {
+        let caps = match helper_regex_run(regex.find_at(input, pos))? {
+            Some(caps) => caps,
+            None => break
+        };
+        let (start, end) = caps[0].unwrap();
+        res.push_str(&input[copied..start]);
+        let mut it = replacement.chars().peekable();
+        while let Some(ch) = it.next() {
+            match (ch, it.peek().copied()) {
+                ('$', Some('$')) => {
+                    it.next();
+                    res.push('$');
+                }
+                ('$', Some(d)) if d.is_ascii_digit() => {
+                    it.next();
+                    let group = d.to_digit(10).unwrap() as usize;
+                    match caps.get(group) {
+                        Some(Some((s, e))) => res.push_str(&input[*s..*e]),
+                        Some(None) => {},
+                        None => return Err(format!("Invalid replacement: the regex has no group {}", group).into())
+                    }
+                }
+                (ch, _) => res.push(ch),
+            }
+        }
+        copied = end;
+        pos = regex.next_start(input, &caps);
+    }
+    res.push_str(&input[copied..]);
+    Ok(Value::Str(res.into()))
+}
+
+fn operator_inc(v: ValueList, _names: &NamePool) -> ValueResult {
+    match numeric::arith(Op::Add, &v[0], &Value::Num(1f64)) {
+        Some(n) => Ok(n),
+        None => type_err!("number", v[0]),
+    }
+}
+
+fn operator_dec(v: ValueList, _names: &NamePool) -> ValueResult {
+    match numeric::arith(Op::Sub, &v[0], &Value::Num(1f64)) {
+        Some(n) => Ok(n),
+        None => type_err!("number", v[0]),
+    }
+}
+
+// Truncates toward zero, NaN and infinities have no integer value
+fn core_to_int(v: ValueList, _names: &NamePool) -> ValueResult {
+    if let Value::BigInt(_) = v[0] {
+        return Ok(v[0].clone())
+    }
+    match numeric::to_f64(&v[0]) {
+        Some(n) if n.is_finite() => Ok(Value::Num(n.trunc())),
+        _ => type_err!("finite number", v[0]),
+    }
+}
+
+// Integers are already floats, fractions become the nearest float
+fn core_to_float(v: ValueList, _names: &NamePool) -> ValueResult {
+    match numeric::to_f64(&v[0]) {
+        Some(n) => Ok(Value::Num(n)),
+        None => type_err!("number", v[0]),
+    }
+}
+
+fn pred_big_int(v: ValueList, _names: &NamePool) -> ValueResult {
+    predicate_op! {v;
+        Value::BigInt(_) => Ok(Value::True);
+        Ok(Value::False)
+    }
+}
+
+fn core_complex(v: ValueList, _names: &NamePool) -> ValueResult {
+    match (numeric::to_f64(&v[0]), numeric::to_f64(&v[1])) {
+        (Some(re), Some(im)) => Ok(Value::Complex(re, im)),
+        (Some(_), None) => type_err!("real number", v[1]),
+        _ => type_err!("real number", v[0]),
+    }
+}
+
+fn core_real(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Complex(re, _) => Ok(Value::Num(*re)),
+        x if numeric::is_real(x) => Ok(x.clone()),
+        x => type_err!("number", x),
+    }
+}
+
+fn core_imag(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Complex(_, im) => Ok(Value::Num(*im)),
+        x if numeric::is_real(x) => Ok(Value::Num(0f64)),
+        x => type_err!("number", x),
+    }
+}
+
+fn core_clamp(v: ValueList, names: &NamePool) -> ValueResult {
+    if let Some(x) = v.iter().find(|x| !numeric::is_real(x)) {
+        return type_err!("real number", x)
+    }
+    let (x, lo, hi) = (&v[0], &v[1], &v[2]);
+    if numeric::compare(lo, hi) == Some(Ordering::Greater) {
+        return Err(format!("Invalid clamp bounds, {} is above {}", Printer::str_name(lo, names), Printer::str_name(hi, names)).into())
+    }
+    Ok(match (numeric::compare(x, lo), numeric::compare(x, hi)) {
+        (Some(Ordering::Less), _) => lo.clone(),
+        (_, Some(Ordering::Greater)) => hi.clone(),
+        _ => x.clone(),
+    })
+}
+
+/// `a + (b - a) * t`, exact when all three are
+fn core_lerp(v: ValueList, _names: &NamePool) -> ValueResult {
+    let (a, b, t) = (&v[0], &v[1], &v[2]);
+    let res = numeric::arith(Op::Sub, b, a)
+        .and_then(|diff| numeric::arith(Op::Mul, &diff, t))
+        .and_then(|step| numeric::arith(Op::Add, a, &step));
+    match res {
+        Some(res) => Ok(res),
+        None => type_err!("number", v.iter().find(|x| !numeric::is_number(x)).unwrap()),
+    }
+}
+
+/// A base between 2 and 36, digits past 9 are the letters a to z
+fn helper_radix(value: &Value) -> Result<u32, error::Error> {
+    match value {
+        Value::Num(n) if n.fract() == 0.0 && (2.0..=36.0).contains(n) => Ok(*n as u32),
+        x => type_err!("base between 2 and 36", x),
+    }
+}
+
+fn core_parse_radix(v: ValueList, _names: &NamePool) -> ValueResult {
+    let radix = helper_radix(&v[1])?;
+    let string = match &v[0] {
+        Value::Str(s) => s.inner(),
+        x => return type_err!("string", x),
+    };
+    let (negative, digits) = match string.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, string),
+    };
+    if digits.is_empty() {
+        return Err(format!("No digits to parse in base {}", radix).into())
+    }
+    let mut n: i128 = 0;
+    for ch in digits.chars() {
+        let digit = match ch.to_digit(radix) {
+            Some(digit) => digit as i128,
+            None => return Err(format!("Invalid digit {} in base {}", ch, radix).into()),
+        };
+        n = match n.checked_mul(radix as i128).and_then(|n| n.checked_add(digit)) {
+            Some(n) => n,
+            None => return Err(format!("Number {} is too big", string).into()),
+        };
+    }
+    Ok(numeric::integer(if negative { -n } else { n }))
+}
+
+fn core_format_radix(v: ValueList, _names: &NamePool) -> ValueResult {
+    let radix = helper_radix(&v[1])?;
+    let n = match &v[0] {
+        // the cast would saturate, 2^127 is the first float past the i128 range
+        Value::Num(n) if n.fract() == 0.0 && n.is_finite() => match *n {
+            n if (-(2f64.powi(127))..2f64.powi(127)).contains(&n) => n as i128,
+            n => return Err(format!("Number {} is too big", n).into()),
+        },
+        Value::BigInt(n) => *n,
+        x => return type_err!("integer", x),
+    };
+    let mut digits = vec![];
+    let mut rest = n.unsigned_abs();
+    loop {
+        digits.push(std::char::from_digit((rest % radix as u128) as u32, radix).unwrap());
+        rest /= radix as u128;
+        if rest == 0 {
+            break
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    Ok(Value::Str(digits.iter().rev().collect::<String>().into()))
+}
+
+fn core_numerator(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Rational(n, _) => Ok(numeric::integer(*n as i128)),
+        Value::Num(n) if n.fract() == 0.0 => Ok(Value::Num(*n)),
+        Value::BigInt(_) => Ok(v[0].clone()),
+        x => type_err!("rational", x),
+    }
+}
+
+fn core_denominator(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Rational(_, d) => Ok(numeric::integer(*d as i128)),
+        Value::Num(n) if n.fract() == 0.0 => Ok(Value::Num(1f64)),
+        Value::BigInt(_) => Ok(Value::Num(1f64)),
+        x => type_err!("rational", x),
+    }
+}
+
+thread_local! {
+    /// Lazy elements are forced in batches of this many, set by `set-lazy-chunk!`
+    static LAZY_CHUNK: std::cell::Cell<usize> = const { std::cell::Cell::new(32) };
+}
+
+/// Sets how many lazy elements are forced at a time and gives back the previous size,
+/// a size of 1 forces nothing past the last element asked for
+fn core_set_lazy_chunk(v: ValueList, _names: &NamePool) -> ValueResult {
+    let size = match helper_index(&v[0])? {
+        0 => return type_err!("positive integer", v[0].clone()),
+        size => size
+    };
+    Ok(Value::Num(LAZY_CHUNK.with(|chunk| chunk.replace(size)) as f64))
+}
+
+/// Realizes at most `limit` elements of a sequence, tails are forced one after
+/// the other without going back through eval for natively built sequences.
+/// Lazy elements are forced a whole chunk at a time, counted from `seq`, so the
+/// tails up to the end of the chunk holding the last element are forced too
+fn helper_realize(seq: &Value, limit: usize) -> Result<ValueList, error::Error> {
+    let chunk = LAZY_CHUNK.with(|chunk| chunk.get());
+    let mut result: ValueList = Vec::new();
+    let mut seq = seq.clone();
+    while result.len() < limit {
+        match seq {
+            Value::Lazy{env, eval, data} => {
+                result.push(data.head.clone());
+                if result.len() == limit {
+                    helper_force_ahead(Value::Lazy{env, eval, data}, (chunk - limit % chunk) % chunk)?;
+                    break
+                }
+                seq = data.force_tail(&env, eval)?;
+            }
+            Value::List(ls) => {
+                let n = ls.len().min(limit - result.len());
+                result.extend_from_slice(&ls[..n]);
+                break
+            }
+            Value::PVector(vector) => {
+                let n = limit - result.len();
+                result.extend(vector.iter().take(n).cloned());
+                break
+            }
+            Value::Nil => break,
+            x => {
+                result.push(x);
+                break
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Forces the next `count` tails of a lazy sequence, they are kept for whoever walks it next
+fn helper_force_ahead(mut seq: Value, count: usize) -> Result<(), error::Error> {
+    for _ in 0..count {
+        seq = match seq {
+            Value::Lazy{env, eval, data} => data.force_tail(&env, eval)?,
+            _ => break
+        };
+    }
+    Ok(())
+}
+
+fn core_collect(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::List(_) => Ok(v[0].clone()),
+        Value::Nil => Ok(Value::Nil),
+        Value::Lazy{..} | Value::PVector(_) => Ok(helper_realize(&v[0], usize::MAX)?.into()),
         x => type_err!("list", x),
     }
 }
 
+fn core_take(v: ValueList, _names: &NamePool) -> ValueResult {
+    let n = helper_index(&v[0])?;
+    match &v[1] {
+        Value::List(_) | Value::Nil | Value::Lazy{..} | Value::PVector(_) => Ok(helper_realize(&v[1], n)?.into()),
+        x => type_err!("sequence", x),
+    }
+}
+
+// An f64 has at most 17 significant digits, so more than this many decimals are all zeros
+const MAX_DECIMALS: usize = 100;
+
+// Rounds half away from zero to the given decimals and groups the integer digits
+// by thousands, e.g. (format-number 1234.5 2) gives "1,234.50"
+fn core_format_number(v: ValueList, _names: &NamePool) -> ValueResult {
+    let n = match &v[0] {
+        Value::Num(n) => *n,
+        x => return type_err!("number", x)
+    };
+    let decimals = helper_index(&v[1])?;
+    if decimals > MAX_DECIMALS {
+        return Err(format!("format-number supports at most {} decimals, got {}", MAX_DECIMALS, decimals).into())
+    }
+    let sep = match v.get(2) {
+        None => ",".to_string(),
+        Some(Value::Str(s)) => s.inner().to_owned(),
+        Some(Value::Char(c)) => c.to_string(),
+        Some(x) => return type_err!("string", x)
+    };
+    if !n.is_finite() {
+        return Ok(Value::Str(n.to_string().into()))
+    }
+    let scale = 10f64.powi(decimals as i32);
+    let rounded = if scale.is_finite() && (n * scale).is_finite() { (n * scale).round() / scale } else { n };
+    let digits = format!("{:.*}", decimals, rounded.abs());
+    let (int, frac) = match digits.find('.') {
+        Some(i) => digits.split_at(i),
+        None => (digits.as_str(), ""),
+    };
+    let mut res = String::new();
+    if rounded < 0.0 && digits.chars().any(|c| c != '0' && c != '.') {
+        res.push('-');
+    }
+    for (i, ch) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            res.push_str(&sep);
+        }
+        res.push(ch);
+    }
+    res.push_str(frac);
+    Ok(Value::Str(res.into()))
+}
+
 fn core_format(v: ValueList, names: &NamePool) -> ValueResult {
     if let Value::Str(format) = &v[0] {
         let mut iter = format.inner().chars().peekable();
@@ -698,7 +2155,9 @@ fn core_format(v: ValueList, names: &NamePool) -> ValueResult {
                                 '@' => {
                                     let mut sep: Option<String> = None;
                                     match iter.peek() {
-                                        Some(ch) if *ch == '}' => {},
+                                        Some(ch) if *ch == '}' => {
+                                            iter.next();
+                                        },
                                         Some(_) => {
                                             let mut sep_ = String::new();
                                             loop {
@@ -717,19 +2176,14 @@ fn core_format(v: ValueList, names: &NamePool) -> ValueResult {
                                     match v.get(current) {
                                         Some(e) => {
                                             if let Value::List(l) = e {
-                                                let mut it = l.into_iter();
-                                                if let Some(expr) = it.next() {
-                                                    if debug {
-                                                        result.push_str(&format!("{}", Printer::repr_name(expr, names)))
-                                                    }else{
-                                                        result.push_str(&format!("{}", Printer::str_name(expr, names)))
+                                                for (i, expr) in l.iter().enumerate() {
+                                                    if let (Some(sep), true) = (&sep, i > 0) {
+                                                        result.push_str(sep)
                                                     }
-                                                };
-                                                for expr in it {
-                                                    if let Some(sep) = sep.clone() {
-                                                        result.push_str(& if debug {format!("{}{:?}", sep, Printer::repr_name(expr, names))} else {format!("{}{}", sep, Printer::str_name(expr, names))})
+                                                    if debug {
+                                                        result.push_str(&Printer::repr_name(expr, names))
                                                     }else{
-                                                        result.push_str(& if debug {format!("{:?}", Printer::repr_name(expr, names))} else {format!("{}", Printer::str_name(expr, names))})
+                                                        result.push_str(&Printer::str_name(expr, names))
                                                     }
                                                 }
                                             } else {
@@ -762,38 +2216,135 @@ fn core_format(v: ValueList, names: &NamePool) -> ValueResult {
 }
 
 fn core_join(v: ValueList, names: &NamePool) -> ValueResult {
-    let sep = if let Value::Str(sep) = &v[0] {
-        sep
-    } else {
-        return type_err!("string", v[0]);
+    // (join list) and (join nil list) concatenate without a separator
+    let (sep, list) = n_args! { v;
+        1 => ("", &v[0]),
+        _ => match &v[0] {
+            Value::Str(sep) => (sep.inner(), &v[1]),
+            Value::Nil => ("", &v[1]),
+            x => return type_err!("string", x)
+        }
     };
 
     let mut result = String::new();
-    if let Value::List(list) = &v[1] {
+    if let Value::List(list) = list {
         let mut it = list.into_iter();
         if let Some(x) = it.next() {
             result.push_str(&format!("{}", Printer::str_name(x, names)))
         }
         for expr in it {
-            result.push_str(&format!("{}{}", sep.inner(), Printer::str_name(expr, names)))
+            result.push_str(&format!("{}{}", sep, Printer::str_name(expr, names)))
         }
         Ok(Value::Str(result.into()))
-    } else if let Value::Nil = &v[1] {
+    } else if let Value::Nil = list {
         Ok(Value::Str("".into()))
     } else {
-        type_err!("list", v[1])
+        type_err!("list", list)
+    }
+}
+
+/// Joins a list that must hold only strings
+fn helper_join_strings(list: &Value, sep: &str) -> ValueResult {
+    let list = match list {
+        Value::List(list) => list,
+        Value::Nil => return Ok(Value::Str("".into())),
+        x => return type_err!("list", x)
+    };
+    let mut result = String::new();
+    for (i, expr) in list.iter().enumerate() {
+        if i > 0 {
+            result.push_str(sep);
+        }
+        match expr {
+            Value::Str(s) => result.push_str(s.inner()),
+            x => return type_err!("string", x)
+        }
+    }
+    Ok(Value::Str(result.into()))
+}
+
+fn core_unlines(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_join_strings(&v[0], "\n")
+}
+
+fn core_unwords(v: ValueList, _names: &NamePool) -> ValueResult {
+    helper_join_strings(&v[0], " ")
+}
+
+/// The name `ns/name` of a two argument `symbol` or `keyword`, qualified names
+/// are interned whole so they compare like any other name. A nil namespace
+/// leaves the name unqualified
+fn helper_qualified_name(ns: &Value, name: &Value, names: &NamePool) -> Result<Name, error::Error> {
+    let name = match name {
+        Value::Str(s) if !s.inner().is_empty() => s.inner(),
+        x => return type_err!("non-empty string", x),
+    };
+    match ns {
+        Value::Nil => Ok(names.add(name)),
+        Value::Str(ns) if !ns.inner().is_empty() => Ok(names.add(&format!("{}/{}", ns.inner(), name))),
+        x => type_err!("non-empty string", x),
     }
 }
 
 fn core_symbol(v: ValueList, names: &NamePool) -> ValueResult {
+    if v.len() == 2 {
+        return Ok(Value::Sym(helper_qualified_name(&v[0], &v[1], names)?))
+    }
     match &v[0] {
         Value::Str(s) => Ok(Value::Sym(names.add(s.inner()))),
         Value::Sym(_) => Ok(v[0].clone()),
+        Value::Keyword(k) => Ok(Value::Sym(*k)),
         x => type_err!("string", x),
     }
 }
 
+thread_local! {
+    /// Turned off by `disable-assertions!`, the assertions then pass their value through unchecked
+    static ASSERTIONS_ENABLED: std::cell::Cell<bool> = const { std::cell::Cell::new(true) };
+}
+
+fn assertions_enabled() -> bool {
+    ASSERTIONS_ENABLED.with(|enabled| enabled.get())
+}
+
+fn core_enable_assertions(_v: ValueList, _names: &NamePool) -> ValueResult {
+    ASSERTIONS_ENABLED.with(|enabled| enabled.set(true));
+    Ok(Value::Nil)
+}
+
+fn core_disable_assertions(_v: ValueList, _names: &NamePool) -> ValueResult {
+    ASSERTIONS_ENABLED.with(|enabled| enabled.set(false));
+    Ok(Value::Nil)
+}
+
+/// Splits a qualified name like `mod/foo` at its first `/`, names without
+/// one (or made of nothing else, like `/`) have no namespace
+fn helper_split_qualified(value: &Value, names: &NamePool) -> Result<(Option<String>, String), error::Error> {
+    let full = match value {
+        Value::Sym(name) | Value::Keyword(name) => names.get(*name),
+        x => return type_err!("symbol or keyword", x),
+    };
+    match full.split_once('/') {
+        Some((ns, name)) if !ns.is_empty() && !name.is_empty() => Ok((Some(ns.to_owned()), name.to_owned())),
+        _ => Ok((None, full)),
+    }
+}
+
+fn core_name(v: ValueList, names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(_) => Ok(v[0].clone()),
+        x => Ok(Value::Str(helper_split_qualified(x, names)?.1.into())),
+    }
+}
+
+fn core_namespace(v: ValueList, names: &NamePool) -> ValueResult {
+    Ok(helper_split_qualified(&v[0], names)?.0.map_or(Value::Nil, |ns| Value::Str(ns.into())))
+}
+
 fn core_assert(v: ValueList, _names: &NamePool) -> ValueResult {
+    if !assertions_enabled() {
+        return Ok(v[0].clone())
+    }
     n_args! { v;
         1 => {
             if v[0].is_nil() {
@@ -820,6 +2371,181 @@ fn core_assert(v: ValueList, _names: &NamePool) -> ValueResult {
     }
 }
 
+// Like (assert actual expected) but the failure shows both values
+fn core_assert_eq(v: ValueList, names: &NamePool) -> ValueResult {
+    if !assertions_enabled() || helper_equal(&v[0], &v[1]) {
+        return Ok(v[0].clone())
+    }
+    let diff = format!("expected {} but got {}", Printer::repr_name(&v[1], names), Printer::repr_name(&v[0], names));
+    match v.get(2) {
+        Some(message) => Err(format!("{}: {}", Printer::str_name(message, names), diff).into()),
+        None => Err(format!("Assertion failed: {}", diff).into()),
+    }
+}
+
+// (assert-that value pred) fails showing the value unless (pred value) holds
+fn core_assert_that(v: ValueList, names: &NamePool) -> ValueResult {
+    if !assertions_enabled() || !v[1].apply(vec![v[0].clone()], names)?.is_false() {
+        return Ok(v[0].clone())
+    }
+    let failed = format!("{} doesn't satisfy the predicate", Printer::repr_name(&v[0], names));
+    match v.get(2) {
+        Some(message) => Err(format!("{}: {}", Printer::str_name(message, names), failed).into()),
+        None => Err(format!("Assertion failed: {}", failed).into()),
+    }
+}
+
+thread_local! {
+    /// Tests registered by `deftest`, in definition order
+    static TESTS: RefCell<Vec<(Name, Value)>> = const { RefCell::new(vec![]) };
+    /// Failures recorded by `is` since the last test started
+    static TEST_FAILURES: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+/// Registers a test thunk, replacing an earlier test with the same name
+pub fn register_test(name: Name, test: Value) {
+    TESTS.with(|tests| {
+        let mut tests = tests.borrow_mut();
+        match tests.iter_mut().find(|(n, _)| *n == name) {
+            Some(entry) => entry.1 = test,
+            None => tests.push((name, test)),
+        }
+    })
+}
+
+pub fn record_failure(message: String) {
+    TEST_FAILURES.with(|failures| failures.borrow_mut().push(message))
+}
+
+fn core_run_tests(_v: ValueList, names: &NamePool) -> ValueResult {
+    let tests = TESTS.with(|tests| tests.borrow().clone());
+    let mut failed = 0;
+    for (name, test) in tests.iter() {
+        TEST_FAILURES.with(|failures| failures.borrow_mut().clear());
+        if let Err(err) = test.apply(vec![], names) {
+            record_failure(format!("raised: {}", Printer::str_error(&err, names)));
+        }
+        let failures = TEST_FAILURES.with(|failures| failures.replace(vec![]));
+        if !failures.is_empty() {
+            failed += 1;
+            write_out(&format!("FAIL {}\n", names.get(*name)))?;
+            for failure in failures.iter() {
+                write_out(&format!("  {}\n", failure))?;
+            }
+        }
+    }
+    write_out(&format!("Ran {} tests: {} passed, {} failed\n", tests.len(), tests.len() - failed, failed))?;
+    Ok(if failed == 0 { Value::True } else { Value::False })
+}
+
+thread_local! {
+    /// The struct built by `error`, shared so `error?` can tell it apart from user structs
+    static ERROR_STRUCT: Rc<types::StructData> = Rc::new(types::StructData {
+        name: crate::names::builtin::ERROR,
+        fields: vec![crate::names::builtin::TAG, crate::names::builtin::PAYLOAD],
+    });
+}
+
+pub fn is_error(value: &Value) -> bool {
+    match value {
+        Value::Struct(id, _) => ERROR_STRUCT.with(|def| Rc::ptr_eq(id, def)),
+        _ => false
+    }
+}
+
+fn core_error(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Keyword(_) => (),
+        x => return type_err!("keyword", x)
+    }
+    let payload = v.get(1).cloned().unwrap_or(Value::Nil);
+    Ok(Value::Struct(ERROR_STRUCT.with(|def| def.clone()), Rc::new(vec![v[0].clone(), payload])))
+}
+
+fn core_is_error(v: ValueList, _names: &NamePool) -> ValueResult {
+    if !is_error(&v[0]) {
+        return Ok(Value::False)
+    }
+    match (&v[0], v.get(1)) {
+        (Value::Struct(_, data), Some(tag)) => Ok((&data[0] == tag).into()),
+        _ => Ok(Value::True)
+    }
+}
+
+fn core_doc(v: ValueList, names: &NamePool) -> ValueResult {
+    let (name, doc) = match &v[0] {
+        Value::Func{func, ..} => (func.name, func.doc.clone()),
+        Value::NatFunc(f) => (Some(f.name), None),
+        x => return type_err!("function", x)
+    };
+    write_out(&format!("{}\n", name.map(|name| names.get(name)).unwrap_or_else(|| "fn".to_string())))?;
+    write_out(&format!("  {}\n", doc.as_deref().unwrap_or("No documentation")))?;
+    Ok(Value::Nil)
+}
+
+fn core_fn_arity(v: ValueList, names: &NamePool) -> ValueResult {
+    let arity = match &v[0] {
+        Value::Func{func, ..} => &func.arity,
+        Value::NatFunc(f) => &f.arity,
+        x => return type_err!("function", x)
+    };
+    Ok(match arity {
+        Arity::Exact(n) => vec![Value::Keyword(names.add("exact")), Value::Num(*n as f64)],
+        Arity::Min(n) => vec![Value::Keyword(names.add("min")), Value::Num(*n as f64)],
+        Arity::Range(min, max) => vec![Value::Keyword(names.add("range")), Value::Num(*min as f64), Value::Num(*max as f64)],
+    }.into())
+}
+
+thread_local! {
+    /// The outermost env, where globals are defined, set once at startup
+    static GLOBAL_ENV: RefCell<Option<Env>> = const { RefCell::new(None) };
+}
+
+/// Gives the natives that look up or define globals the env holding them
+pub fn set_global_env(env: Env) {
+    GLOBAL_ENV.with(|global| *global.borrow_mut() = Some(env))
+}
+
+fn helper_global_env() -> Result<Env, error::Error> {
+    GLOBAL_ENV.with(|global| global.borrow().clone())
+        .ok_or_else(|| "There is no global environment".into())
+}
+
+/// Names bound in the outermost env, sorted alphabetically
+fn helper_global_symbols(names: &NamePool) -> Result<Vec<(String, Name)>, error::Error> {
+    let global = helper_global_env()?;
+    let mut symbols = global.data.borrow().keys().map(|name| (names.get(*name), *name)).collect::<Vec<_>>();
+    symbols.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(symbols)
+}
+
+fn core_all_symbols(_v: ValueList, names: &NamePool) -> ValueResult {
+    Ok(helper_global_symbols(names)?.into_iter().map(|(_, name)| Value::Sym(name)).collect::<ValueList>().into())
+}
+
+fn core_intern(v: ValueList, names: &NamePool) -> ValueResult {
+    // keywords are accepted too, so a config map's keys can become globals
+    let name = match &v[0] {
+        Value::Sym(s) | Value::Keyword(s) => *s,
+        Value::Str(s) => names.add(s.inner()),
+        x => return type_err!("symbol", x)
+    };
+    helper_global_env()?.set(name, v[1].clone());
+    Ok(Value::Sym(name))
+}
+
+fn core_apropos(v: ValueList, names: &NamePool) -> ValueResult {
+    let part = match &v[0] {
+        Value::Str(s) => s.inner().to_string(),
+        Value::Sym(s) | Value::Keyword(s) => names.get(*s),
+        x => return type_err!("string", x)
+    };
+    Ok(helper_global_symbols(names)?.into_iter()
+        .filter(|(name, _)| name.contains(&part))
+        .map(|(_, name)| Value::Sym(name))
+        .collect::<ValueList>().into())
+}
+
 fn core_make_struct(v: ValueList, _names: &NamePool) -> ValueResult {
     let struct_id = match &v[0] {
         Value::StructDef(s) => s,
@@ -865,6 +2591,44 @@ fn core_assert_struct(v: ValueList, _names: &NamePool) -> ValueResult {
     Ok((Rc::ptr_eq(struct_id, check_id)).into())
 }
 
+fn core_sb_new(v: ValueList, names: &NamePool) -> ValueResult {
+    let mut string = String::new();
+    if let Some(value) = v.first() {
+        helper_push_str(&mut string, value, names);
+    }
+    Ok(Value::StringBuilder(Rc::new(RefCell::new(string))))
+}
+
+/// Appends every argument after the builder the way `str` would, returning the builder
+fn core_sb_append(v: ValueList, names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::StringBuilder(string) => {
+            let mut string = string.borrow_mut();
+            for value in &v[1..] {
+                helper_push_str(&mut string, value, names);
+            }
+        }
+        x => return type_err!("string builder", x),
+    }
+    Ok(v[0].clone())
+}
+
+fn core_sb_append_char(v: ValueList, _names: &NamePool) -> ValueResult {
+    match (&v[0], &v[1]) {
+        (Value::StringBuilder(string), Value::Char(chr)) => string.borrow_mut().push(*chr),
+        (Value::StringBuilder(_), x) => return type_err!("char", x),
+        (x, _) => return type_err!("string builder", x),
+    }
+    Ok(v[0].clone())
+}
+
+fn core_sb_to_string(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::StringBuilder(string) => Ok(Value::Str(string.borrow().as_str().into())),
+        x => type_err!("string builder", x),
+    }
+}
+
 pub fn core_string_append_char(v: ValueList, _names: &NamePool) -> ValueResult {
     match (&v[0], &v[1]) {
         (Value::Str(s), Value::Char(chr)) => {
@@ -874,6 +2638,163 @@ pub fn core_string_append_char(v: ValueList, _names: &NamePool) -> ValueResult {
     }
 }
 
+pub fn core_string_head(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(s) => Ok(s.head().into()),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("string", x),
+    }
+}
+
+/// Everything after the first char, sharing the original string
+pub fn core_string_tail(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(s) => Ok(s.tail().into()),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("string", x),
+    }
+}
+
+pub fn core_string_concat(v: ValueList, _names: &NamePool) -> ValueResult {
+    let mut res = String::new();
+    for value in v.iter() {
+        match value {
+            Value::Str(s) => res.push_str(s.inner()),
+            Value::Nil => (),
+            x => return type_err!("string", x),
+        }
+    }
+    Ok(Value::Str(res.into()))
+}
+
+/// Lines ending in `\n` or `\r\n`, a final line ending doesn't add an empty line
+pub fn core_string_split_lines(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(s) => Ok(s.inner().lines().map(Value::from).collect::<ValueList>().into()),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("string", x),
+    }
+}
+
+pub fn core_string_split_words(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(s) => Ok(s.inner().split_whitespace().map(Value::from).collect::<ValueList>().into()),
+        Value::Nil => Ok(Value::Nil),
+        x => type_err!("string", x),
+    }
+}
+
+/// Renders rows of cells with every column padded to its widest cell.
+/// Takes an optional separator (a single space by default) and alignment,
+/// `:left` or `:right`. Left aligned cells in the last column aren't padded
+pub fn core_string_table(v: ValueList, names: &NamePool) -> ValueResult {
+    let separator = match v.get(1) {
+        Some(Value::Str(s)) => s.inner().to_owned(),
+        Some(Value::Nil) => String::new(),
+        None => " ".to_owned(),
+        Some(x) => return type_err!("string", x),
+    };
+    let right = match v.get(2) {
+        None => false,
+        Some(Value::Keyword(k)) if names.get(*k) == "left" => false,
+        Some(Value::Keyword(k)) if names.get(*k) == "right" => true,
+        Some(x) => return type_err!(":left or :right", x),
+    };
+    let mut rows = vec![];
+    for row in helper_realize(&v[0], usize::MAX)? {
+        let cells = helper_realize(&row, usize::MAX)?;
+        rows.push(cells.iter().map(|cell| Printer::str_name(cell, names)).collect::<Vec<String>>());
+    }
+    let mut widths: Vec<usize> = vec![];
+    for row in rows.iter() {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    let lines = rows.iter().map(|row| {
+        let cells = row.iter().enumerate().map(|(i, cell)| {
+            let pad = " ".repeat(widths[i] - cell.chars().count());
+            if right {
+                format!("{}{}", pad, cell)
+            } else if i + 1 == row.len() {
+                cell.clone()
+            } else {
+                format!("{}{}", cell, pad)
+            }
+        });
+        cells.collect::<Vec<String>>().join(&separator)
+    });
+    Ok(Value::Str(lines.collect::<Vec<String>>().join("\n").into()))
+}
+
+/// Lazy inclusive range of chars by code point, counting down if `to` comes
+/// before `from`. Surrogate code points aren't chars so they are skipped
+fn core_char_range(v: ValueList, names: &NamePool) -> ValueResult {
+    match (&v[0], &v[1]) {
+        (Value::Char(_), Value::Char(_)) => helper_char_range(v, names),
+        (Value::Char(_), x) | (x, _) => type_err!("char", x),
+    }
+}
+
+fn helper_char_range(v: ValueList, names: &NamePool) -> ValueResult {
+    let (from, to) = match (&v[0], &v[1]) {
+        (Value::Char(from), Value::Char(to)) => (*from, *to),
+        _ => unreachable!(),
+    };
+    let tail = if from == to {
+        Value::Nil
+    } else {
+        let next = if from < to {
+            char::from_u32(from as u32 + 1).unwrap_or('\u{E000}')
+        } else {
+            char::from_u32(from as u32 - 1).unwrap_or('\u{D7FF}')
+        };
+        let rest = types::func(names.add("char-range"), Arity::Exact(2), helper_char_range);
+        vater!{ ([rest] [Value::Char(next)] [v[1].clone()]) }
+    };
+    Ok(Value::Lazy {
+        eval: crate::eval, env: EnvStruct::new(None),
+        data: LazyData::new(Value::Char(from), tail, Rc::new(names.clone()))
+    })
+}
+
+/// Lazy sequence of the chars of a string, each step shares the string instead of copying it
+fn core_string_lazy_chars(v: ValueList, names: &NamePool) -> ValueResult {
+    let string = match &v[0] {
+        Value::Str(s) => s,
+        Value::Nil => return Ok(Value::Nil),
+        x => return type_err!("string", x),
+    };
+    let head = match string.head() {
+        Some(ch) => ch,
+        None => return Ok(Value::Nil),
+    };
+    let tail = match string.tail() {
+        Some(rest) => {
+            let next = types::func(names.add("string/lazy-chars"), Arity::Exact(1), core_string_lazy_chars);
+            vater!{ ([next] [Value::Str(rest)]) }
+        }
+        None => Value::Nil,
+    };
+    Ok(Value::Lazy {
+        eval: crate::eval, env: EnvStruct::new(None),
+        data: LazyData::new(Value::Char(head), tail, Rc::new(names.clone()))
+    })
+}
+
+/// The words in reverse order, any run of whitespace between them becomes a single space
+pub fn core_string_reverse_words(v: ValueList, _names: &NamePool) -> ValueResult {
+    match &v[0] {
+        Value::Str(s) => Ok(Value::Str(s.inner().split_whitespace().rev().collect::<Vec<&str>>().join(" ").into())),
+        Value::Nil => Ok(Value::Str("".into())),
+        x => type_err!("string", x),
+    }
+}
+
 pub fn core_char_to_string(v: ValueList, _names: &NamePool) -> ValueResult {
     match &v[0] {
         Value::Char(c) => {
@@ -910,14 +2831,18 @@ pub fn core_string_starts_with(v: ValueList, _names: &NamePool) -> ValueResult {
 pub fn core_chars_slice(v: ValueList, _names: &NamePool) -> ValueResult {
     n_args! { v;
         2 => match (&v[0], &v[1]) {
-            (Value::Str(chars), Value::Num(start)) => {
-                Ok(chars.slice(*start as usize).into())
+            (Value::Str(chars), start) => {
+                Ok(chars.slice(helper_index(start)?).into())
             },
             _ => Err("arguments are invalid".into())
         },
         3 => match (&v[0], &v[1], &v[2]) {
-            (Value::Str(chars), Value::Num(start), Value::Num(end)) => {
-                let slice = &chars.inner()[*start as usize .. *end as usize];
+            (Value::Str(chars), start, end) => {
+                let (start, end) = (helper_index(start)?, helper_index(end)?);
+                let slice = match chars.inner().get(start..end) {
+                    Some(slice) => slice,
+                    None => return Err(format!("Invalid slice {}..{} of a string of {} bytes", start, end, chars.len()).into())
+                };
                 if slice.len() == 0 {
                     Ok(Value::Nil)
                 } else {
@@ -931,8 +2856,12 @@ pub fn core_chars_slice(v: ValueList, _names: &NamePool) -> ValueResult {
 }
 
 pub fn core_keyword(v: ValueList, names: &NamePool) -> ValueResult {
+    if v.len() == 2 {
+        return Ok(Value::Keyword(helper_qualified_name(&v[0], &v[1], names)?))
+    }
     match &v[0] {
         Value::Keyword(_) => Ok(v[0].clone()),
+        Value::Sym(s) => Ok(Value::Keyword(*s)),
         Value::Str(s) => Ok(Value::Keyword(names.add(s.inner()))),
         x => type_err!("string", x.clone())
     }
@@ -955,22 +2884,22 @@ pub fn core_name_from_intern_number(v: ValueList, _names: &NamePool) -> ValueRes
 
 pub fn ns() -> Vec<(&'static str, Arity, fn(ValueList, &NamePool) -> ValueResult)>{
     vec![
-        ("+", Arity::Min(0), |v: Vec<Value>, _| add_mul_op!(+, 0f64, v)),
-        ("*", Arity::Min(0), |v: Vec<Value>, _| add_mul_op!(*, 1f64, v)),
-        ("-", Arity::Min(0), |v: Vec<Value>, _| sub_div_op!(-, Ok(Value::Num(0.)), |a: f64| -a, v)),
-        ("/", Arity::Min(0), |v: Vec<Value>, _| sub_div_op!(/, Err(error::Error::TypeErr("number", None)), |a: f64| 1./a, v)),
-        ("mod", Arity::Exact(2), |v: Vec<Value>, _| match (&v[0], &v[1]) {
-            (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a.rem_euclid(*b))),
-            (a, b) => type_err!("number", Value::from(vec![a.clone(), b.clone()]))
+        ("+", Arity::Min(0), |v: Vec<Value>, _| add_mul_op!(Op::Add, 0f64, v)),
+        ("*", Arity::Min(0), |v: Vec<Value>, _| add_mul_op!(Op::Mul, 1f64, v)),
+        ("-", Arity::Min(0), |v: Vec<Value>, _| sub_div_op!(Op::Sub, Ok(Value::Num(0.)), Value::Num(0.), v)),
+        ("/", Arity::Min(0), |v: Vec<Value>, _| sub_div_op!(Op::Div, Err(error::Error::TypeErr("number", None)), Value::Num(1.), v)),
+        ("mod", Arity::Exact(2), |v: Vec<Value>, _| match numeric::remainder(&v[0], &v[1], true) {
+            Some(n) => Ok(n),
+            None => type_err!("real number", Value::from(vec![v[0].clone(), v[1].clone()]))
         }),
-        ("rem", Arity::Exact(2), |v: Vec<Value>, _| match (&v[0], &v[1]) {
-            (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a % b)),
-            (a, b) => type_err!("number", Value::from(vec![a.clone(), b.clone()]))
+        ("rem", Arity::Exact(2), |v: Vec<Value>, _| match numeric::remainder(&v[0], &v[1], false) {
+            Some(n) => Ok(n),
+            None => type_err!("real number", Value::from(vec![v[0].clone(), v[1].clone()]))
         }),
-        ("<", Arity::Min(0), |v: Vec<Value>, _| ord_op!(<, v)),
-        (">", Arity::Min(0), |v: Vec<Value>, _| ord_op!(>, v)),
-        ("<=", Arity::Min(0), |v: Vec<Value>, _| ord_op!(<=, v)),
-        (">=", Arity::Min(0), |v: Vec<Value>, _| ord_op!(>=, v)),
+        ("<", Arity::Min(0), |v: Vec<Value>, _| ord_op!(Ordering::Less, v)),
+        (">", Arity::Min(0), |v: Vec<Value>, _| ord_op!(Ordering::Greater, v)),
+        ("<=", Arity::Min(0), |v: Vec<Value>, _| ord_op!(Ordering::Less | Ordering::Equal, v)),
+        (">=", Arity::Min(0), |v: Vec<Value>, _| ord_op!(Ordering::Greater | Ordering::Equal, v)),
         ("==", Arity::Min(1), operator_eq),
         ("!=", Arity::Min(1), operator_ne),
         ("str", Arity::Min(0), operator_str),
@@ -982,53 +2911,148 @@ pub fn ns() -> Vec<(&'static str, Arity, fn(ValueList, &NamePool) -> ValueResult
         ("fifth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(4f64)], n)),
         ("sixth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(5f64)], n)),
         ("seventh", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(6f64)], n)),
-        ("eigth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(6f64)], n)),
-        ("nineth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(6f64)], n)),
-        ("tenth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(6f64)], n)),
+        ("eighth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(7f64)], n)),
+        ("ninth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(8f64)], n)),
+        ("tenth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(9f64)], n)),
         ("nth", Arity::Exact(2), operator_nth),
+        // old misspellings kept so existing code keeps working
+        ("eigth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(7f64)], n)),
+        ("nineth", Arity::Exact(1), |v: Vec<Value>, n| operator_nth(vec![v[0].clone(), Value::Num(8f64)], n)),
         ("rest", Arity::Exact(1), operator_tail),
         ("cons", Arity::Exact(2), operator_cons),
+        ("list*", Arity::Min(1), operator_list_star),
         ("rev-cons", Arity::Exact(2), operator_rev_cons),
         ("atom?", Arity::Exact(1), pred_atom),
         ("list?", Arity::Exact(1), pred_list),
         ("nil?", Arity::Exact(1), pred_nil),
         ("number?", Arity::Exact(1), pred_number),
+        ("rational?", Arity::Exact(1), pred_rational),
+        ("complex?", Arity::Exact(1), pred_complex),
+        ("big-int?", Arity::Exact(1), pred_big_int),
         ("string?", Arity::Exact(1), pred_string),
         ("symbol?", Arity::Exact(1), pred_symbol),
         ("function?", Arity::Exact(1), pred_function),
         ("keyword?", Arity::Exact(1), pred_keyword),
         ("hash-map?", Arity::Exact(1), pred_hashmap),
+        ("lazy?", Arity::Exact(1), pred_lazy),
+        ("vector?", Arity::Exact(1), pred_vector),
+        ("string-builder?", Arity::Exact(1), pred_string_builder),
+        ("realized?", Arity::Exact(1), pred_realized),
         ("apply", Arity::Min(2), core_apply),
         ("map", Arity::Exact(2), core_map),
+        ("vector", Arity::Min(0), core_vector),
+        ("vec", Arity::Exact(1), core_vec),
+        ("push", Arity::Exact(2), core_push),
         ("filter", Arity::Exact(2), core_filter),
+        ("remove", Arity::Exact(2), core_remove),
+        ("separate", Arity::Exact(2), core_separate),
         ("flatmap", Arity::Exact(2), core_flatmap),
+        ("keep", Arity::Exact(2), core_keep),
+        ("partition-by", Arity::Exact(2), core_partition_by),
+        ("dedupe", Arity::Exact(1), core_dedupe),
+        ("most-common", Arity::Range(1, 2), core_most_common),
+        ("list/index-of", Arity::Exact(2), core_list_index_of),
+        ("list/last-index-of", Arity::Exact(2), core_list_last_index_of),
+        ("rle-encode", Arity::Exact(1), core_rle_encode),
+        ("rle-decode", Arity::Exact(1), core_rle_decode),
+        ("max-key", Arity::Min(2), core_max_key),
+        ("min-key", Arity::Min(2), core_min_key),
+        ("maximum", Arity::Exact(1), core_maximum),
+        ("minimum", Arity::Exact(1), core_minimum),
+        ("sum", Arity::Exact(1), core_sum),
+        ("product", Arity::Exact(1), core_product),
+        ("reductions", Arity::Range(2, 3), core_reductions),
         ("append", Arity::Min(0), core_append),
         ("time-ms", Arity::Exact(0), core_time_ms),
+        ("mono-ms", Arity::Exact(0), core_mono_ms),
+        ("env-vars", Arity::Exact(0), core_env_vars),
+        ("run-command", Arity::Min(1), core_run_command),
+        ("now", Arity::Exact(0), core_now),
+        ("format-time", Arity::Exact(1), core_format_time),
+        ("parse-time", Arity::Exact(1), core_parse_time),
+        ("rand", Arity::Range(0, 1), core_rand),
+        ("rand-seed", Arity::Exact(1), core_rand_seed),
+        ("uuid", Arity::Exact(0), core_uuid),
         ("println", Arity::Min(0), core_println),
         ("print", Arity::Min(0), core_print),
+        ("pr", Arity::Min(0), core_pr),
+        ("prn", Arity::Min(0), core_prn),
         ("input", Arity::Exact(0), core_input),
+        ("read-all-input", Arity::Exact(0), core_read_all_input),
+        ("read-char", Arity::Exact(0), core_read_char),
         ("repr", Arity::Min(0), core_repr),
+        ("doc", Arity::Exact(1), core_doc),
+        ("fn-arity", Arity::Exact(1), core_fn_arity),
+        ("all-symbols", Arity::Exact(0), core_all_symbols),
+        ("apropos", Arity::Exact(1), core_apropos),
+        ("intern", Arity::Exact(2), core_intern),
+        ("pr-str", Arity::Min(0), core_pr_str),
+        ("print-str", Arity::Min(0), core_print_str),
         ("len", Arity::Exact(1), operator_len),
+        ("read-from", Arity::Exact(2), core_read_from),
         ("read", Arity::Exact(1), core_read),
+        ("parse-data", Arity::Exact(1), core_parse_data),
         ("read-file", Arity::Exact(1), core_read_file),
+        ("open", Arity::Exact(1), core_open),
+        ("read-line!", Arity::Exact(1), core_read_line),
+        ("close!", Arity::Exact(1), core_close),
+        ("regex", Arity::Exact(1), core_regex),
+        ("re-find", Arity::Exact(2), core_re_find),
+        ("re-match", Arity::Exact(2), core_re_match),
+        ("re-find-all", Arity::Exact(2), core_re_find_all),
+        ("re-replace", Arity::Exact(3), core_re_replace),
+        ("re-seq", Arity::Exact(2), core_re_seq),
         ("inc", Arity::Exact(1), operator_inc),
         ("dec", Arity::Exact(1), operator_dec),
+        ("->int", Arity::Exact(1), core_to_int),
+        ("->float", Arity::Exact(1), core_to_float),
+        ("numerator", Arity::Exact(1), core_numerator),
+        ("denominator", Arity::Exact(1), core_denominator),
+        ("complex", Arity::Exact(2), core_complex),
+        ("real", Arity::Exact(1), core_real),
+        ("imag", Arity::Exact(1), core_imag),
+        ("parse-radix", Arity::Exact(2), core_parse_radix),
+        ("format-radix", Arity::Exact(2), core_format_radix),
+        ("clamp", Arity::Exact(3), core_clamp),
+        ("lerp", Arity::Exact(3), core_lerp),
         ("collect", Arity::Exact(1), core_collect),
+        ("set-lazy-chunk!", Arity::Exact(1), core_set_lazy_chunk),
+        ("force-seq", Arity::Exact(1), core_collect),
+        ("take", Arity::Exact(2), core_take),
         ("format", Arity::Min(1), core_format),
-        ("join", Arity::Min(2), core_join),
+        ("format-number", Arity::Range(2, 3), core_format_number),
+        ("join", Arity::Range(1, 2), core_join),
+        ("unlines", Arity::Exact(1), core_unlines),
+        ("unwords", Arity::Exact(1), core_unwords),
         ("hash-map", Arity::Min(0), core_hashmap),
         ("assoc", Arity::Min(1), operator_assoc),
+        ("merge-with", Arity::Min(1), core_merge_with),
+        ("update-keys", Arity::Exact(2), core_update_keys),
+        ("keywordize-keys", Arity::Exact(1), core_keywordize_keys),
+        ("stringify-keys", Arity::Exact(1), core_stringify_keys),
         ("dissoc", Arity::Min(1), operator_dissoc),
         ("get-key", Arity::Exact(2), operator_map_get),
         ("update", Arity::Min(3), operator_map_update),
         ("has-key?", Arity::Exact(2), operator_has_key),
         ("map-keys", Arity::Exact(1), core_map_keys),
-        ("symbol", Arity::Exact(1), core_symbol),
+        ("map-vals", Arity::Exact(1), core_map_vals),
+        ("map-entries", Arity::Exact(1), core_map_entries),
+        ("reduce-kv", Arity::Exact(3), core_reduce_kv),
+        ("symbol", Arity::Range(1, 2), core_symbol),
+        ("name", Arity::Exact(1), core_name),
+        ("namespace", Arity::Exact(1), core_namespace),
         ("make-struct", Arity::Min(1), core_make_struct),
         ("struct-field", Arity::Exact(2), core_member_struct),
+        ("error", Arity::Range(1,2), core_error),
+        ("error?", Arity::Range(1,2), core_is_error),
         ("is-struct?", Arity::Exact(2), core_assert_struct),
         ("assert", Arity::Range(1,3),core_assert),
-        ("keyword", Arity::Exact(1), core_keyword),
+        ("enable-assertions!", Arity::Exact(0), core_enable_assertions),
+        ("disable-assertions!", Arity::Exact(0), core_disable_assertions),
+        ("assert-that", Arity::Range(2, 3), core_assert_that),
+        ("assert-eq", Arity::Range(2, 3), core_assert_eq),
+        ("run-tests", Arity::Exact(0), core_run_tests),
+        ("keyword", Arity::Range(1, 2), core_keyword),
         ("!!name-intern-number!!", Arity::Exact(1), core_keyword_intern_number),
         ("!!symbol-from-intern-number!!", Arity::Exact(1), core_name_from_intern_number),
         ("box", Arity::Exact(1),|v: Vec<Value>, _| Ok(Value::Box(Rc::new(RefCell::new(v[0].clone()))))),
@@ -1058,10 +3082,25 @@ pub fn ns() -> Vec<(&'static str, Arity, fn(ValueList, &NamePool) -> ValueResult
             x => type_err!("list", x.clone()),
         }),
         ("id", Arity::Exact(1), |v: Vec<Value>, _| return Ok(v[0].clone())),
+        ("tap", Arity::Exact(2), core_tap),
+        ("spy", Arity::Exact(1), core_spy),
         ("string/starts-with", Arity::Exact(2), core_string_starts_with),
         ("string/append-char", Arity::Exact(2), core_string_append_char),
+        ("sb-new", Arity::Range(0, 1), core_sb_new),
+        ("sb-append!", Arity::Min(2), core_sb_append),
+        ("sb-append-char!", Arity::Exact(2), core_sb_append_char),
+        ("sb->string", Arity::Exact(1), core_sb_to_string),
         ("string/slice", Arity::Range(2, 3), core_chars_slice),
+        ("string/head", Arity::Exact(1), core_string_head),
+        ("string/lazy-chars", Arity::Exact(1), core_string_lazy_chars),
+        ("string/concat", Arity::Min(0), core_string_concat),
+        ("string/split-lines", Arity::Exact(1), core_string_split_lines),
+        ("string/split-words", Arity::Exact(1), core_string_split_words),
+        ("string/reverse-words", Arity::Exact(1), core_string_reverse_words),
+        ("string/table", Arity::Range(1, 3), core_string_table),
+        ("string/tail", Arity::Exact(1), core_string_tail),
         ("char->string", Arity::Exact(1), core_char_to_string),
+        ("char-range", Arity::Exact(2), core_char_range),
         ("char-list->string", Arity::Exact(1), core_char_list_to_string),
     ]
 }
@@ -15,6 +15,8 @@ pub enum Error {
     MatchErr,
     AssertErr,
     Throw(Option<Value>),
+    /// A thrown value built by `error`, carrying a keyword tag and a payload
+    Custom(Value),
     Trace(Name, Box<Error>)
 }
 
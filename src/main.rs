@@ -5,6 +5,10 @@ mod core;
 mod error;
 mod printer;
 mod names;
+mod regex;
+mod pvec;
+mod pmap;
+mod numeric;
 
 use std::io;
 use std::io::Write;
@@ -25,7 +29,7 @@ use std::collections::HashMap;
 // DONE: Read files in a better way
 // DONE: Pattern errors
 
-use crate::types::{Value, Env, ValueList, FuncData, Arity, LazyData, StructData};
+use crate::types::{Value, Env, ValueList, FuncData, Arity, LazyData, StructData, MapKey};
 use crate::printer::Printer;
 use crate::names::{NamePool, Name};
 use crate::names::builtin as stdname;
@@ -89,6 +93,26 @@ fn quasiquote(ast: Value) -> Value {
     }
 }
 
+/// Builds the call of a threading step with an already evaluated value, quoted
+/// so it isn't evaluated again, as the first or the last argument
+fn thread_step(step: &Value, value: Value, last: bool) -> Value {
+    let value = vater!{ (QUOTE [value]) };
+    match step {
+        Value::List(ls) if last => {
+            let mut call = Vec::with_capacity(ls.len() + 1);
+            call.extend_from_slice(&ls[..]);
+            call.push(value);
+            call.into()
+        }
+        Value::List(ls) => {
+            let mut call = vec![ls[0].clone(), value];
+            call.extend_from_slice(&ls[1..]);
+            call.into()
+        }
+        x => vec![x.clone(), value].into()
+    }
+}
+
 /// Tests if a list is a macro call
 fn is_macro_call(ast: Value, env: Env) -> bool {
     match ast {
@@ -391,9 +415,9 @@ fn match_pattern(pat: Value, expr: Value, env: Env, names: Rc<NamePool>) -> Patt
 
                     let mut all_binds = HashMap::default();
                     for i in (1..l.len()).step_by(2) {
-                        let key = match &l[i] {
-                            Value::Keyword(n) | Value::Sym(n) => *n,
-                            x => return Error(type_err!("keyword"; x.clone()))
+                        let key = match MapKey::from_value(&l[i], &names) {
+                            Some(key) => key,
+                            None => return Error(type_err!("keyword"; l[i].clone()))
                         };
                         let val = match map.get(&key) {
                             Some(val) => val,
@@ -490,8 +514,91 @@ fn match_pattern(pat: Value, expr: Value, env: Env, names: Rc<NamePool>) -> Patt
     }
 }
 
+/// Splits a leading docstring off a `fun` or `defmacro` body
+fn function_doc(body: &[Value]) -> (Option<String>, &Value) {
+    match body {
+        [Value::Str(doc), body, ..] => (Some(doc.inner().to_string()), body),
+        _ => (None, &body[0])
+    }
+}
+
+/// Binds the kind of a caught error and its value in a new env, for `catch` and `try`
+fn catch_env(binding: &Value, err: error::Error, env: Env) -> Result<Env, error::Error> {
+    let (kind, value) = match binding.to_pair() {
+        Some((Value::Sym(kind), Value::Sym(value))) => (kind, value),
+        Some((Value::Sym(_), value)) => return Err(type_err!("symbol"; value)),
+        Some((kind, _)) => return Err(type_err!("symbol"; kind)),
+        None => return Err(pair_err!("catch binding"))
+    };
+    use error::Error::*;
+    let err = if let x @ Trace(_, _) = err {
+        let (_, error) = error::collect_trace(&x);
+        error.clone()
+    } else {
+        err
+    };
+    let local_env = types::EnvStruct::new(Some(env));
+    let sym = match err {
+        Throw(val) => {
+            local_env.set(value, val.unwrap_or(Value::Nil)); "ThrowError"
+        }
+        Custom(val) => {
+            local_env.set(value, val); "CustomError"
+        }
+        Reason(val) => {
+            local_env.set(value, Value::Str(val.into())); "ReasonError"
+        }
+        ArgErr(name, _arity, got) => {
+            let name = match name {
+                Some(name) => Value::Sym(name),
+                None => Value::Nil
+            };
+            local_env.set(value, vater!{ ([name] [Value::Num(got as f64)]) }); "ArityError"
+        }
+        KwArgErr(name) => {
+            let name = match name {
+                Some(name) => Value::Sym(name),
+                None => Value::Nil
+            };
+            local_env.set(value, vater!{ ([name]) }); "KwargsError"
+        }
+        TypeErr(ty, got) => {
+            local_env.set(value, vater!{ ([Value::Str(ty.into())] [got.unwrap_or(Value::Nil)]) }); "TypeError"
+        }
+        CallErr(got) => {
+            local_env.set(value, got.unwrap_or(Value::Nil)); "CallError"
+        }
+        BindErr(name) => {
+            local_env.set(value, Value::Sym(name)); "NameError"
+        }
+        KeyErr(name) => {
+            local_env.set(value, Value::Sym(name)); "KeyError"
+        }
+        PairErr(name) => {
+            local_env.set(value, name.into()); "PairError"
+        }
+        _ => "Error"
+    };
+    local_env.set(kind, Value::Str(sym.into()));
+    Ok(local_env)
+}
+
 /// Evaluate an expression
-fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
+///
+/// The user function it last tail-called is added to the backtrace of any error.
+/// Tail calls share a frame, so only the innermost of a chain of them shows up
+fn eval(ast: Value, env: Env, names: Rc<NamePool>) -> ValueResult {
+    let mut frame = None;
+    match eval_frame(ast, env, names, &mut frame) {
+        Err(err) => match frame {
+            Some(name) => Err(error::Error::Trace(name, Box::new(err))),
+            None => Err(err)
+        },
+        ok => ok
+    }
+}
+
+fn eval_frame(mut ast: Value, mut env: Env, names: Rc<NamePool>, frame: &mut Option<Name>) -> ValueResult {
     let ret: ValueResult;
 
     'tco: loop {
@@ -569,6 +676,82 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             }
                         }
                     }
+                    Value::Sym(sym) if sym == &stdname::COND_PIPE_ || sym == &stdname::COND_PIPEPE_ => {
+                        if l.len() < 2 {
+                            return Err(arg_err!(*sym; 1..; l.len() - 1))
+                        }
+                        if l.len() % 2 != 0 {
+                            return Err(pair_err!("test and step"))
+                        }
+                        let last = sym == &stdname::COND_PIPEPE_;
+                        let mut value = eval(l[1].clone(), env.clone(), names.clone())?;
+                        for pair in l[2..].chunks(2) {
+                            if !eval(pair[0].clone(), env.clone(), names.clone())?.is_false() {
+                                value = eval(thread_step(&pair[1], value, last), env.clone(), names.clone())?;
+                            }
+                        }
+                        Ok(value)
+                    }
+                    Value::Sym(sym) if sym == &stdname::AS_PIPE_ => {
+                        if l.len() < 3 {
+                            return Err(arg_err!(stdname::AS_PIPE_; 2..; l.len() - 1))
+                        }
+                        let name = match &l[2] {
+                            Value::Sym(name) => *name,
+                            x => return Err(type_err!("symbol"; x.clone())),
+                        };
+                        let local_env = types::EnvStruct::new(Some(env.clone()));
+                        local_env.set(name, eval(l[1].clone(), env.clone(), names.clone())?);
+                        match l[3..].split_last() {
+                            Some((last, steps)) => {
+                                for step in steps {
+                                    let value = eval(step.clone(), local_env.clone(), names.clone())?;
+                                    local_env.set(name, value);
+                                }
+                                ast = last.clone();
+                                env = local_env;
+                                continue 'tco;
+                            }
+                            None => local_env.get(name),
+                        }
+                    }
+                    Value::Sym(sym) if sym == &stdname::DOTO => {
+                        if l.len() < 2 {
+                            return Err(arg_err!(stdname::DOTO; 1..; l.len() - 1))
+                        }
+                        let value = eval(l[1].clone(), env.clone(), names.clone())?;
+                        for step in l[2..].iter() {
+                            eval(thread_step(step, value.clone(), false), env.clone(), names.clone())?;
+                        }
+                        Ok(value)
+                    }
+                    // Shows the form itself next to its value, on stderr so output stays clean
+                    Value::Sym(sym) if sym == &stdname::DBG => {
+                        if l.len() != 2 {
+                            return Err(arg_err!(stdname::DBG; 1; l.len() - 1))
+                        }
+                        let value = eval(l[1].clone(), env.clone(), names.clone())?;
+                        eprintln!("{} => {}", Printer::repr_read(&l[1], &names), Printer::repr_name(&value, &names));
+                        Ok(value)
+                    }
+                    Value::Sym(sym) if sym == &stdname::WITH_OUT_STR => {
+                        let (res, out) = core::capture_output(|| {
+                            l[1..].iter().try_for_each(|expr| eval(expr.clone(), env.clone(), names.clone()).map(|_| ()))
+                        });
+                        res.map(|_| Value::Str(out.into()))
+                    }
+                    Value::Sym(sym) if sym == &stdname::WITH_IN_STR => {
+                        if l.len() < 2 {
+                            return Err(arg_err!(stdname::WITH_IN_STR; 1..; l.len() - 1))
+                        }
+                        let text = match eval(l[1].clone(), env.clone(), names.clone())? {
+                            Value::Str(s) => s.inner().to_string(),
+                            x => return Err(type_err!("string"; x))
+                        };
+                        core::provide_input(text, || {
+                            l[2..].iter().try_fold(Value::Nil, |_, expr| eval(expr.clone(), env.clone(), names.clone()))
+                        })
+                    }
                     Value::Sym(sym) if sym == &stdname::PARTIALR_ => {
                         if l.len() < 2 { return Ok(Value::Nil); }
                         if l.len() == 2 { return Ok(l[1].clone()) }
@@ -586,6 +769,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                 rest_param: Some(stdname::IT_),
                                 ast: body.into(),
                                 name: None,
+                                doc: None,
                                 arity: Arity::Min(0), names,
                                 is_macro: false,
                             }),
@@ -594,7 +778,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                     Value::Sym(sym) if sym == &stdname::APPLY => {
                         let len = l.len() - 1;
                         if l.len() < 3 {
-                            return Ok(Value::Nil);
+                            return Err(arg_err!(stdname::APPLY; 2..; l.len() - 1))
                         }
                         let mut args = l[2..len].iter()
                             .map(|v| eval(v.clone(), env.clone(), names.clone()))
@@ -631,6 +815,36 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             ast = l[2].clone();
                             continue 'tco;
                         }
+                    Value::Sym(sym) if sym == &stdname::IF_NOT => 
+                        if l.len() != 4 {
+                            Err(arg_err!(stdname::IF_NOT; 3; l.len() - 1))
+                        }else if eval(l[1].clone(), env.clone(), names.clone())?.is_false() {
+                            ast = l[2].clone();
+                            continue 'tco;
+                        } else {
+                            ast = l[3].clone();
+                            continue 'tco;
+                        }
+                    Value::Sym(sym) if sym == &stdname::WHEN_NOT => {
+                        if l.len() < 2 {
+                            return Err(arg_err!(stdname::WHEN_NOT; 1..; l.len() - 1))
+                        }
+                        if !eval(l[1].clone(), env.clone(), names.clone())?.is_false() {
+                            return Ok(Value::Nil)
+                        }
+                        match l[2..].split_last() {
+                            Some((last, body)) => {
+                                for expr in body {
+                                    eval(expr.clone(), env.clone(), names.clone())?;
+                                }
+                                ast = last.clone();
+                                continue 'tco;
+                            }
+                            None => Ok(Value::Nil),
+                        }
+                    }
+                    // The body was read so it has to be valid, but it never runs
+                    Value::Sym(sym) if sym == &stdname::COMMENT => Ok(Value::Nil),
                     Value::Sym(sym) if sym == &stdname::COND => {
                         let mut res: ValueResult = Ok(Value::Nil);
                         for pair in l[1..].iter() {
@@ -719,6 +933,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                         } else if let Value::Sym(s) = &l[1] {
                             if let Some(params) = &l[2].to_vec() {
                                 let (req, opt, key, rest, arity) = from_lambda_list((&**params).clone())?;
+                                let (doc, body) = function_doc(&l[3..]);
                                 let func = Value::Func{
                                     env: env.clone(),
                                     eval,
@@ -727,8 +942,9 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                         opt_params: opt,
                                         has_kwargs: key,
                                         rest_param: if let Some(s) = rest { Some(s) } else { None },
-                                        ast: l[3].clone(),
+                                        ast: body.clone(),
                                         name: Some(*s),
+                                        doc,
                                         arity, names,
                                         is_macro: false,
                                     }),
@@ -741,12 +957,60 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                         } else {
                             Err(type_err!("symbol"; l[1].clone()))
                         }
+                    Value::Sym(sym) if sym == &stdname::DEFTEST =>
+                        if l.len() < 3 {
+                            Err(arg_err!(stdname::DEFTEST; 2..; l.len() - 1))
+                        } else if let Value::Sym(s) = &l[1] {
+                            let mut body = vec![Value::Sym(stdname::BLOCK)];
+                            body.extend_from_slice(&l[2..]);
+                            core::register_test(*s, Value::Func{
+                                env: env.clone(),
+                                eval,
+                                func: Rc::new(FuncData {
+                                    params: vec![],
+                                    opt_params: vec![],
+                                    has_kwargs: false,
+                                    rest_param: None,
+                                    ast: body.into(),
+                                    name: Some(*s),
+                                    doc: None,
+                                    arity: Arity::Exact(0), names,
+                                    is_macro: false,
+                                }),
+                            });
+                            Ok(Value::Nil)
+                        } else {
+                            Err(type_err!("symbol"; l[1].clone()))
+                        }
+                    Value::Sym(sym) if sym == &stdname::IS => {
+                        if l.len() != 2 && l.len() != 3 {
+                            return Err(arg_err!(stdname::IS; 1..2; l.len() - 1))
+                        }
+                        // failures are recorded for `run-tests` instead of aborting the test
+                        let failure = match eval(l[1].clone(), env.clone(), names.clone()) {
+                            Ok(val) if !val.is_false() => None,
+                            Ok(val) => Some(format!("{} was {}", Printer::repr_read(&l[1], &names), Printer::repr_name(&val, &names))),
+                            Err(err) => Some(format!("{} raised: {}", Printer::repr_read(&l[1], &names), Printer::str_error(&err, &names))),
+                        };
+                        match failure {
+                            None => Ok(Value::True),
+                            Some(failure) => {
+                                let failure = match l.get(2) {
+                                    Some(message) => format!("{}: {}", Printer::str_name(&eval(message.clone(), env.clone(), names.clone())?, &names), failure),
+                                    None => failure,
+                                };
+                                core::record_failure(failure);
+                                Ok(Value::False)
+                            }
+                        }
+                    }
                     Value::Sym(sym) if sym == &stdname::DEFMACRO =>
                         if l.len() < 4 {
                             Err(arg_err!(stdname::DEFMACRO; 3; l.len() - 1))
                         } else if let Value::Sym(s) = &l[1] {
                             if let Some(params) = &l[2].to_vec() {
                                 let (req, opt, key, rest, arity) = from_lambda_list((&**params).clone())?;
+                                let (doc, body) = function_doc(&l[3..]);
                                 let func = Value::Func{
                                     env: env.clone(),
                                     eval,
@@ -755,8 +1019,9 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                         opt_params: opt,
                                         has_kwargs: key,
                                         rest_param: if let Some(s) = rest { Some(s) } else { None },
-                                        ast: l[3].clone(),
+                                        ast: body.clone(),
                                         name: Some(*s),
+                                        doc,
                                         arity, names,
                                         is_macro: true,
                                     }),
@@ -786,6 +1051,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                     rest_param: rest,
                                     ast: body.into(),
                                     name: None,
+                                    doc: None,
                                     arity, names,
                                     is_macro: false,
                                 }),
@@ -857,6 +1123,49 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             }).collect::<Result<Vec<(Name, Value)>, error::Error>>()?;
                         }
                     }
+                    Value::Sym(sym) if sym == &stdname::WITH_OPEN => {
+                        let len = l.len();
+                        if len < 3 {
+                            return Err(arg_err!(stdname::WITH_OPEN; 2..; len - 1))
+                        }
+                        let binds = match l[1].to_vec() {
+                            Some(binds) => binds,
+                            None => return Err(type_err!("list"; l[1].clone()))
+                        };
+
+                        let local_env = types::EnvStruct::new(Some(env.clone()));
+                        let mut handles: ValueList = vec![];
+                        let mut res: ValueResult = Ok(Value::Nil);
+                        for pair in binds.iter() {
+                            res = match pair.to_pair() {
+                                Some((Value::Sym(name), value)) => eval(value, local_env.clone(), names.clone()).map(|handle| {
+                                    local_env.set(name, handle.clone());
+                                    handles.push(handle);
+                                    Value::Nil
+                                }),
+                                Some((x, _)) => Err(type_err!("symbol"; x)),
+                                None => Err(pair_err!("with-open binding"))
+                            };
+                            if res.is_err() {
+                                break
+                            }
+                        }
+                        if res.is_ok() {
+                            for expr in l[2..].iter() {
+                                res = eval(expr.clone(), local_env.clone(), names.clone());
+                                if res.is_err() {
+                                    break
+                                }
+                            }
+                        }
+                        // files are closed even if the body failed
+                        for handle in handles.iter().rev() {
+                            if let Value::File(file) = handle {
+                                file.borrow_mut().take();
+                            }
+                        }
+                        res
+                    }
                     Value::Sym(sym) if sym == &stdname::MODULE => {
                         let len = l.len();
                         if len < 2 {
@@ -908,6 +1217,13 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             return Err(type_err!("symbol"; l[1].clone()))
                         };
 
+                        // exports are bound as `alias/name` when imported with `:as alias`
+                        let prefix = match &l[3..] {
+                            [] => *mod_name,
+                            [Value::Keyword(k), Value::Sym(alias)] if names.get(*k) == "as" => *alias,
+                            _ => return Err(format!("Invalid import options {}", Printer::repr_read(&l[3..].to_vec().into(), &names)).into())
+                        };
+
                         let mut modules: ValueList = vec![];
                         if match env.get(stdname::SP_MODULES)? {
                             Value::List(l) => {
@@ -938,10 +1254,9 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             return Err("Couldn't open file".to_string().into())
                         }
 
-                        let file_names = Rc::new(NamePool::new());
-
+                        // the module shares the importer's names, its symbols must resolve to the same globals
                         let file_content = format!("({})", contents);
-                        let mut tk = parser::Reader::new(&file_content, &file_names);
+                        let mut tk = parser::Reader::new(&file_content, &names);
                         let exprs = if let Ok(tok) = tk.next_token() {
                             match match tk.parse_expr(tok) {
                                 parser::ParserResult::Expr(expr) => expr,
@@ -966,17 +1281,17 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                 Value::List(l) if l.len() > 0 => {
                                     match &l[0] {
                                         Value::Sym(s) if s == &stdname::EXPORTS => exports = (&l[1..]).to_vec(),
-                                        _ => {eval(expr.clone(), local_env.clone(), file_names.clone())?;}
+                                        _ => {eval(expr.clone(), local_env.clone(), names.clone())?;}
                                     }
                                 }
-                                _ => {eval(expr.clone(), local_env.clone(), file_names.clone())?;}
+                                _ => {eval(expr.clone(), local_env.clone(), names.clone())?;}
                             }
                             
                         };
                         for expr in exports {
                             match &expr {
                                 Value::Sym(s) => {
-                                    let exported = format!("{}/{}", names.get(*mod_name), names.get(*s));
+                                    let exported = format!("{}/{}", names.get(prefix), names.get(*s));
                                     env.set(names.add(&exported), local_env.get(*s)?)
                                 }
                                 x => return Err(type_err!("symbol"; x.clone()))
@@ -1004,7 +1319,17 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                         continue 'tco;
                     }
                     Value::Sym(ref sym) if sym == &stdname::THROW => {
-                        Err(error::Error::Throw(l.get(1).cloned()))
+                        match l.get(1) {
+                            Some(expr) => {
+                                let val = eval(expr.clone(), env.clone(), names.clone())?;
+                                if core::is_error(&val) {
+                                    Err(error::Error::Custom(val))
+                                } else {
+                                    Err(error::Error::Throw(Some(val)))
+                                }
+                            }
+                            None => Err(error::Error::Throw(None))
+                        }
                     }
                     Value::Sym(ref sym) if sym == &stdname::CATCH => {
                         if l.len() == 3 {
@@ -1019,66 +1344,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             match eval(l[1].clone(), env.clone(), names.clone()) {
                                 Ok(val) => Ok(val),
                                 Err(err) => {
-                                    let local_env = types::EnvStruct::new(Some(env.clone()));
-                                    if let Some((kind, value)) = &l[2].to_pair() {
-                                        let kind = match kind {
-                                            Value::Sym(n) => n,
-                                            _ => return Err(type_err!("symbol"; kind.clone()))
-                                        };
-                                        let value = match value {
-                                            Value::Sym(n) => n,
-                                            _ => return Err(type_err!("symbol"; value.clone()))
-                                        };
-                                        use error::Error::*;
-                                        let err = if let x @ Trace(_, _) = err {
-                                            let (_, error) = error::collect_trace(&x);
-                                            error.clone()
-                                        } else {
-                                            err
-                                        };
-                                        let sym = match err {
-                                            Throw(val) => {
-                                                local_env.set(value.clone(), val.unwrap_or(Value::Nil)); "ThrowError"
-                                            }
-                                            Reason(val) => {
-                                                local_env.set(value.clone(), Value::Str(val.into())); "ReasonError"
-                                            }
-                                            ArgErr(name, _arity, got) => {
-                                                let name = match name {
-                                                    Some(name) => Value::Sym(name),
-                                                    None => Value::Nil
-                                                };
-                                                local_env.set(value.clone(), vater!{ ([name] [Value::Num(got as f64)]) }); "ArityError"
-                                            }
-                                            KwArgErr(name) => {
-                                                let name = match name {
-                                                    Some(name) => Value::Sym(name),
-                                                    None => Value::Nil
-                                                };
-                                                local_env.set(value.clone(), vater!{ ([name]) }); "KwargsError"
-                                            }
-                                            TypeErr(ty, got) => {
-                                                local_env.set(value.clone(), vater!{ ([Value::Str(ty.into())] [got.unwrap_or(Value::Nil)]) }); "TypeError"
-                                            }
-                                            CallErr(got) => {
-                                                local_env.set(value.clone(), got.unwrap_or(Value::Nil)); "CallError"
-                                            }
-                                            BindErr(name) => {
-                                                local_env.set(value.clone(), Value::Sym(name)); "NameError"
-                                            }
-                                            KeyErr(name) => {
-                                                local_env.set(value.clone(), Value::Sym(name)); "KeyError"
-                                            }
-                                            PairErr(name) => {
-                                                local_env.set(value.clone(), name.into()); "PairError"
-                                            }
-                                            _ => "Error"
-                                        };
-                                        local_env.set(kind.clone(), Value::Str(sym.into()));
-                                    } else {
-                                        return Err(pair_err!("catch binding"))
-                                    }
-                                    env = local_env;
+                                    env = catch_env(&l[2], err, env.clone())?;
                                     ast = l[3].clone();
                                     continue 'tco;
                                 }
@@ -1087,6 +1353,50 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             return Err(arg_err!(stdname::CATCH; 2..3; l.len() - 1))
                         }
                     }
+                    Value::Sym(ref sym) if sym == &stdname::TRY => {
+                        if l.len() < 2 {
+                            return Err(arg_err!(stdname::TRY; 1..; l.len() - 1))
+                        }
+                        let mut handler = None;
+                        let mut cleanup = None;
+                        for clause in l[2..].iter() {
+                            match clause.to_vec() {
+                                Some([Value::Sym(s), binding, body @ ..]) if *s == stdname::CATCH && handler.is_none() => handler = Some((binding, body)),
+                                Some([Value::Sym(s), body @ ..]) if *s == stdname::FINALLY && cleanup.is_none() => cleanup = Some(body),
+                                _ => return Err(format!("Invalid try clause {}", Printer::repr_read(clause, &names)).into())
+                            }
+                        }
+                        let mut res = eval(l[1].clone(), env.clone(), names.clone());
+                        if let (Err(err), Some((binding, body))) = (&res, handler) {
+                            res = catch_env(binding, err.clone(), env.clone()).and_then(|local_env| {
+                                let mut val = Value::Nil;
+                                for expr in body.iter() {
+                                    val = eval(expr.clone(), local_env.clone(), names.clone())?;
+                                }
+                                Ok(val)
+                            });
+                        }
+                        // the cleanup runs last, even when the handler itself failed,
+                        // and an error raised by the cleanup replaces the previous result
+                        if let Some(body) = cleanup {
+                            for expr in body.iter() {
+                                eval(expr.clone(), env.clone(), names.clone())?;
+                            }
+                        }
+                        res
+                    }
+                    Value::Sym(ref sym) if sym == &stdname::TRY_OR => {
+                        if l.len() != 3 {
+                            return Err(arg_err!(stdname::TRY_OR; 2; l.len() - 1))
+                        }
+                        match eval(l[1].clone(), env.clone(), names.clone()) {
+                            Ok(val) => Ok(val),
+                            Err(_) => {
+                                ast = l[2].clone();
+                                continue 'tco;
+                            }
+                        }
+                    }
                     Value::Sym(ref sym) if sym == &stdname::LAZY_CONS => {
                         if l.len() < 3 {
                             return Err(arg_err!(stdname::LAZY_CONS; 2; l.len() - 1))
@@ -1094,7 +1404,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                         let head = eval(l[1].clone(), env.clone(), names.clone())?;
                         Ok(Value::Lazy{
                             eval, env,
-                            data: Rc::new(LazyData { head, tail: l[2].clone(), names: names.clone() })
+                            data: LazyData::new(head, l[2].clone(), names.clone())
                         })
                     }
                     Value::Keyword(key) => {
@@ -1103,7 +1413,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                         }
                         match eval(l[1].clone(), env.clone(), names.clone())? {
                             Value::Map(map) => {
-                                match map.get(key) {
+                                match map.get(&MapKey::Keyword(*key)) {
                                     Some(val) => Ok(val.clone()),
                                     None => {
                                         ast = match l.get(2) {
@@ -1127,11 +1437,7 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                             let args = list[1..].to_vec();
                             match func {
                                 Value::NatFunc(f) => {
-                                    if !match f.arity {
-                                        Arity::Exact(n) => args.len() == n.into(),
-                                        Arity::Min(n) => args.len() >= n.into(),
-                                        Arity::Range(min, max) => min as usize <= args.len() && args.len() <= max.into(),
-                                    } {
+                                    if !f.arity.accepts(args.len()) {
                                         return Err(error::Error::ArgErr(Some(f.name), f.arity.clone(), args.len() as u16))
                                     }
                                     match (f.func)(args, &names) {
@@ -1150,10 +1456,12 @@ fn eval(mut ast: Value, mut env: Env, names: Rc<NamePool>) -> ValueResult {
                                 } => {
                                     let a = &(func).ast;
                                     let local_env = types::EnvStruct::bind(Some(fenv.clone()), &func, args, *eval)?;
+                                    *frame = func.name;
                                     ast = a.clone();
                                     env = local_env.clone();
                                     continue 'tco;
                                 },
+                                Value::Keyword(_) => func.apply(args, &names),
                                 _ => Err(error::Error::CallErr(Some(func.clone()))),
                             }
                         }
@@ -1194,6 +1502,7 @@ fn main() {
     repl_env.set(stdname::SP_DIR_NAME, ".".into());
     repl_env.set(stdname::SP_MODULES, Value::Nil);
     repl_env.set(stdname::NIL, Value::Nil);
+    core::set_global_env(repl_env.clone());
 
     {
         let name_printf = pool.add("printf");
@@ -1,22 +1,38 @@
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::collections::HashMap;
 
 /// Interned name id
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Name(pub i32);
 
-/// Stores the interned names (aka hospital)
+/// Stores the interned names (aka hospital), clones share the same names
+#[derive(Clone)]
 pub struct NamePool {
-    names: RefCell<[Vec<String>; 7]>
+    names: Rc<RefCell<[Vec<String>; 7]>>,
+    /// Every name added so far, so interning a name again is a single hash lookup
+    /// instead of matching the builtins and searching its level
+    cache: Rc<RefCell<HashMap<String, Name>>>,
 }
 
 impl NamePool {
     pub fn new() -> Self {
         NamePool {
-            names: RefCell::new([vec![], vec![], vec![], vec![], vec![], vec![], vec![]])
+            names: Rc::new(RefCell::new([vec![], vec![], vec![], vec![], vec![], vec![], vec![]])),
+            cache: Rc::new(RefCell::new(HashMap::new())),
         }
     }
 
     pub fn add(&self, name: &str) -> Name {
+        if let Some(cached) = self.cache.borrow().get(name) {
+            return *cached
+        }
+        let interned = self.intern(name);
+        self.cache.borrow_mut().insert(name.to_owned(), interned);
+        interned
+    }
+
+    fn intern(&self, name: &str) -> Name {
         let mut names = self.names.borrow_mut();
         if let Some(name) = get_builtin_name(name) {
             return name
@@ -30,13 +46,13 @@ impl NamePool {
                 let index = std::mem::transmute::<[u8; 4], u32>(bytes);
                 (index as i32, 0)
             },
-            4..=5 => (NamePool::search_vec(&mut names[0], name), 1),
-            6..=7 => (NamePool::search_vec(&mut names[1], name), 2),
-            8..=11 => (NamePool::search_vec(&mut names[2], name), 3),
-            12..=15 => (NamePool::search_vec(&mut names[3], name), 4),
-            16..=23 => (NamePool::search_vec(&mut names[4], name), 5),
-            24..=31 => (NamePool::search_vec(&mut names[5], name), 6),
-            _ => (NamePool::search_vec(&mut names[6], name), 7),
+            4..=5 => (NamePool::push_name(&mut names[0], name), 1),
+            6..=7 => (NamePool::push_name(&mut names[1], name), 2),
+            8..=11 => (NamePool::push_name(&mut names[2], name), 3),
+            12..=15 => (NamePool::push_name(&mut names[3], name), 4),
+            16..=23 => (NamePool::push_name(&mut names[4], name), 5),
+            24..=31 => (NamePool::push_name(&mut names[5], name), 6),
+            _ => (NamePool::push_name(&mut names[6], name), 7),
         };
         let name = index << 3 | level;
         Name(name)
@@ -52,21 +68,20 @@ impl NamePool {
         if level == 0 {
             unsafe {
                 let bytes: [u8; 3] = [(index & 0xff) as u8, ((index & 0x00ff00) >> 8) as u8, ((index & 0xff0000) >> 16) as u8];
-                return String::from_utf8_unchecked(bytes.to_vec())
+                // names shorter than 3 bytes are padded with zeros
+                let len = bytes.iter().position(|b| *b == 0).unwrap_or(3);
+                return String::from_utf8_unchecked(bytes[..len].to_vec())
             }
         }
         let level = level - 1;
         names[level as usize].get(index as usize).unwrap_or(&"[Invalid Name]".to_string()).clone()
     }
 
-    fn search_vec(names: &mut Vec<String>, name: &str) -> i32 {
-        if let Some(pos) = names.iter().position(|n| n == name) {
-            pos as i32
-        } else {
-            let n = names.len();
-            names.push(name.to_owned());
-            n as i32
-        }
+    // only called for names missing from the cache, so the name is always new
+    fn push_name(names: &mut Vec<String>, name: &str) -> i32 {
+        let n = names.len();
+        names.push(name.to_owned());
+        n as i32
     }
 }
 
@@ -137,8 +152,8 @@ buitin_names! {
     "fifth" => FIFTH = -39,
     "sixth" => SIXTH = -40,
     "seventh" => SEVENTH = -41,
-    "eigth" => EIGTH = -42,
-    "nineth" => NINETH = -43,
+    "eighth" => EIGHTH = -42,
+    "ninth" => NINTH = -43,
     "tenth" => TENTH = -44,
     "nth" => NTH = -45,
     "str" => STR = -46,
@@ -168,4 +183,24 @@ buitin_names! {
     "=>>" => PIPEPE_ = -70,
     "p>" => PARTIALR_ = -71,
     "apply" => APPLY = -72,
+    "with-open" => WITH_OPEN = -73,
+    "deftest" => DEFTEST = -74,
+    "is" => IS = -75,
+    "error" => ERROR = -76,
+    "tag" => TAG = -77,
+    "payload" => PAYLOAD = -78,
+    "try-or" => TRY_OR = -79,
+    "try" => TRY = -80,
+    "finally" => FINALLY = -81,
+    "doto" => DOTO = -85,
+    "cond=>" => COND_PIPE_ = -86,
+    "cond=>>" => COND_PIPEPE_ = -87,
+    "as=>" => AS_PIPE_ = -88,
+    "if-not" => IF_NOT = -89,
+    "when-not" => WHEN_NOT = -90,
+    "remove" => REMOVE = -91,
+    "comment" => COMMENT = -92,
+    "with-out-str" => WITH_OUT_STR = -93,
+    "dbg" => DBG = -94,
+    "with-in-str" => WITH_IN_STR = -95,
 }
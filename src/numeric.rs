@@ -0,0 +1,215 @@
+/// Arithmetic across the number types
+///
+/// Numbers are floats unless they are exact fractions or integers too big for a
+/// float to hold exactly. A fraction is kept reduced with a positive denominator
+/// and one that comes out whole is an integer again, and big integers that come
+/// back into the float range are floats again, so every integer has a single
+/// representation. Fractions and integers stay exact when combined with each
+/// other, up to the size of `i128`, anything else falls back to floats. Integers
+/// are floats too, so a whole float such as `2.0` is the integer `2` and stays
+/// exact next to a fraction, while `2.5` makes the result a float. Complex
+/// numbers take over any operation they are part of.
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use crate::types::Value;
+
+#[derive(Clone, Copy)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Every integer up to this magnitude is exact as a float
+const MAX_EXACT: f64 = (1u64 << 53) as f64;
+
+/// `None` when the result doesn't fit, which only happens around `i128::MIN`
+fn gcd(mut a: i128, mut b: i128) -> Option<i128> {
+    while b != 0 {
+        let r = a.checked_rem(b)?;
+        a = b;
+        b = r;
+    }
+    a.checked_abs()
+}
+
+/// An integer as a float when that is exact, as a big integer otherwise
+pub fn integer(n: i128) -> Value {
+    if n.unsigned_abs() <= 1 << 53 {
+        Value::Num(n as f64)
+    } else {
+        Value::BigInt(n)
+    }
+}
+
+/// `n/d` reduced, an integer when it is whole, a float when too big for the fraction type
+pub fn rational(n: i128, d: i128) -> Value {
+    if d == 0 {
+        return Value::Num(n as f64 / 0.0)
+    }
+    let float = || Value::Num(n as f64 / d as f64);
+    let (mut n, mut d) = match gcd(n, d) {
+        Some(g) => (n / g, d / g),
+        None => return float(),
+    };
+    if d < 0 {
+        match (n.checked_neg(), d.checked_neg()) {
+            (Some(neg_n), Some(neg_d)) => {
+                n = neg_n;
+                d = neg_d;
+            }
+            _ => return float(),
+        }
+    }
+    if d == 1 {
+        return integer(n)
+    }
+    match (i64::try_from(n), i64::try_from(d)) {
+        (Ok(n), Ok(d)) => Value::Rational(n, d),
+        _ => Value::Num(n as f64 / d as f64),
+    }
+}
+
+/// The fraction a number is exactly equal to, integers are fractions over 1
+fn as_ratio(value: &Value) -> Option<(i128, i128)> {
+    match value {
+        Value::Rational(n, d) => Some((*n as i128, *d as i128)),
+        x => Some((as_int(x)?, 1)),
+    }
+}
+
+/// The integer a number is exactly equal to
+fn as_int(value: &Value) -> Option<i128> {
+    match value {
+        Value::BigInt(n) => Some(*n),
+        Value::Num(n) if n.fract() == 0.0 && n.abs() <= MAX_EXACT => Some(*n as i128),
+        _ => None,
+    }
+}
+
+/// Both numbers as fractions if at least one of them can't be a float, whole
+/// floats count as the integers they are equal to
+fn exact_pair(a: &Value, b: &Value) -> Option<((i128, i128), (i128, i128))> {
+    if matches!(a, Value::Rational(..) | Value::BigInt(_)) || matches!(b, Value::Rational(..) | Value::BigInt(_)) {
+        Some((as_ratio(a)?, as_ratio(b)?))
+    } else {
+        None
+    }
+}
+
+pub fn to_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Num(n) => Some(*n),
+        Value::Rational(n, d) => Some(*n as f64 / *d as f64),
+        Value::BigInt(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+pub fn is_number(value: &Value) -> bool {
+    matches!(value, Value::Num(_) | Value::Rational(..) | Value::BigInt(_) | Value::Complex(..))
+}
+
+/// Numbers that can be ordered, every number but complex ones
+pub fn is_real(value: &Value) -> bool {
+    matches!(value, Value::Num(_) | Value::Rational(..) | Value::BigInt(_))
+}
+
+fn to_complex(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Complex(re, im) => Some((*re, *im)),
+        x => Some((to_f64(x)?, 0.0)),
+    }
+}
+
+fn complex_op(op: Op, (a, b): (f64, f64), (c, d): (f64, f64)) -> Value {
+    let (re, im) = match op {
+        Op::Add => (a + c, b + d),
+        Op::Sub => (a - c, b - d),
+        Op::Mul => (a * c - b * d, a * d + b * c),
+        Op::Div => {
+            let norm = c * c + d * d;
+            ((a * c + b * d) / norm, (b * c - a * d) / norm)
+        }
+    };
+    Value::Complex(re, im)
+}
+
+fn float_op(op: Op, a: f64, b: f64) -> f64 {
+    match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div => a / b,
+    }
+}
+
+fn int_op(op: Op, a: i128, b: i128) -> Option<Value> {
+    match op {
+        Op::Add => a.checked_add(b).map(integer),
+        Op::Sub => a.checked_sub(b).map(integer),
+        Op::Mul => a.checked_mul(b).map(integer),
+        Op::Div => None,
+    }
+}
+
+fn ratio_op(op: Op, (a, b): (i128, i128), (c, d): (i128, i128)) -> Option<Value> {
+    let (n, d) = match op {
+        Op::Add => (a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?, b.checked_mul(d)?),
+        Op::Sub => (a.checked_mul(d)?.checked_sub(c.checked_mul(b)?)?, b.checked_mul(d)?),
+        Op::Mul => (a.checked_mul(c)?, b.checked_mul(d)?),
+        Op::Div => (a.checked_mul(d)?, b.checked_mul(c)?),
+    };
+    Some(rational(n, d))
+}
+
+/// `a op b`, `None` if either of them isn't a number
+pub fn arith(op: Op, a: &Value, b: &Value) -> Option<Value> {
+    if let (Value::Num(x), Value::Num(y)) = (a, b) {
+        let res = float_op(op, *x, *y);
+        // a float this big may have been rounded, so integers are done again exactly
+        let rounded = res.abs() >= MAX_EXACT && !matches!(op, Op::Div);
+        if !rounded {
+            return Some(Value::Num(res))
+        }
+        return Some(as_int(a).zip(as_int(b)).and_then(|(x, y)| int_op(op, x, y)).unwrap_or(Value::Num(res)))
+    }
+    if matches!(a, Value::Complex(..)) || matches!(b, Value::Complex(..)) {
+        return Some(complex_op(op, to_complex(a)?, to_complex(b)?))
+    }
+    if let Some(exact) = exact_pair(a, b).and_then(|(x, y)| ratio_op(op, x, y)) {
+        return Some(exact)
+    }
+    Some(Value::Num(float_op(op, to_f64(a)?, to_f64(b)?)))
+}
+
+/// The euclidean `a mod b`, which is never negative, or when not `euclid` the
+/// remainder with the sign of `a`. Exact for integers, `None` if either isn't a real number
+pub fn remainder(a: &Value, b: &Value, euclid: bool) -> Option<Value> {
+    let float = |x: f64, y: f64| Value::Num(if euclid { x.rem_euclid(y) } else { x % y });
+    if let (Value::Num(x), Value::Num(y)) = (a, b) {
+        return Some(float(*x, *y))
+    }
+    if !is_real(a) || !is_real(b) {
+        return None
+    }
+    let exact = as_int(a).zip(as_int(b)).and_then(|(x, y)| {
+        if euclid { x.checked_rem_euclid(y) } else { x.checked_rem(y) }
+    });
+    match exact {
+        Some(n) => Some(integer(n)),
+        None => Some(float(to_f64(a)?, to_f64(b)?)),
+    }
+}
+
+/// Orders two real numbers, `None` if either isn't one or is NaN
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    if let Some(((a, b), (c, d))) = exact_pair(a, b) {
+        if let (Some(x), Some(y)) = (a.checked_mul(d), c.checked_mul(b)) {
+            return Some(x.cmp(&y))
+        }
+    }
+    to_f64(a)?.partial_cmp(&to_f64(b)?)
+}
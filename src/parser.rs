@@ -1,9 +1,9 @@
-use std::iter::Peekable;
 use std::str::Chars;
 use std::collections::HashMap;
 
-use crate::types::{Value, ValueList};
+use crate::types::{Value, ValueList, SliceString};
 use crate::names::{NamePool};
+use crate::numeric;
 
 macro_rules! vater_args {
     ( $($rest:tt)* ) => { vater!( $($rest)* ) };
@@ -40,6 +40,9 @@ pub enum Token {
     True,
     False,
     Number(f64),
+    Rational(i128, i128),
+    Integer(i128),
+    Complex(f64, f64),
     Char(char),
     Symbol(String),
     Keyword(String),
@@ -49,12 +52,46 @@ pub enum Token {
 
 pub type ReaderMacroStore = HashMap<String, (String, bool)>;
 
+/// Peekable chars of the source that can tell how much of it is left
+struct Source<'a> {
+    chars: Chars<'a>,
+    peeked: Option<Option<char>>,
+}
+
+impl<'a> Source<'a> {
+    fn peek(&mut self) -> Option<&char> {
+        let chars = &mut self.chars;
+        self.peeked.get_or_insert_with(|| chars.next()).as_ref()
+    }
+
+    fn remaining(&self) -> usize {
+        let peeked = match self.peeked {
+            Some(Some(c)) => c.len_utf8(),
+            _ => 0,
+        };
+        self.chars.as_str().len() + peeked
+    }
+}
+
+impl<'a> Iterator for Source<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.chars.next(),
+        }
+    }
+}
+
 /// The tokenizer reads an input and produces expressions
 pub struct Reader<'a, 'h> {
-    chars: Peekable<Chars<'a>>,
+    chars: Source<'a>,
     names: &'h NamePool,
     current_line: i32,
-    pub macros: ReaderMacroStore
+    pub macros: ReaderMacroStore,
+    /// Literals already read, so repeated strings share one allocation
+    strings: HashMap<String, SliceString>
 }
 
 pub struct ReaderError {
@@ -83,15 +120,21 @@ macro_rules! token_try {
 
 impl<'a, 'h> Reader<'a, 'h> {
     /// Create a new Tokenizer from a source string
-    pub fn new(source: &'a String, names: &'h NamePool) -> Self {
+    pub fn new(source: &'a str, names: &'h NamePool) -> Self {
         Reader {
-            chars: source.chars().peekable(),
+            chars: Source { chars: source.chars(), peeked: None },
             names,
             current_line: 1,
-            macros: HashMap::default()
+            macros: HashMap::default(),
+            strings: HashMap::default()
         }
     }
 
+    /// Bytes of the source that haven't been read yet
+    pub fn remaining(&self) -> usize {
+        self.chars.remaining()
+    }
+
     /// Read the next Token in the stream
     pub fn next_token(&mut self) -> Result<Token, ReaderError> {
         loop{
@@ -131,7 +174,7 @@ impl<'a, 'h> Reader<'a, 'h> {
                     '-' => match self.chars.peek() {
                         Some(c) => match *c {
                             '0' ..= '9' => match self.read_number(chr) {
-                                Ok(num) => return Ok(Token::Number(num)),
+                                Ok(num) => return Ok(num),
                                 Err(err) => return reader_err!(err, self.current_line)
                             }
                             _ => match self.read_symbol('-') {
@@ -143,7 +186,7 @@ impl<'a, 'h> Reader<'a, 'h> {
                     },
                     '0' ..= '9' => {
                         match self.read_number(chr) {
-                            Ok(num) => return Ok(Token::Number(num)),
+                            Ok(num) => return Ok(num),
                             Err(err) => return reader_err!(err, self.current_line)
                         }
                     }
@@ -160,6 +203,11 @@ impl<'a, 'h> Reader<'a, 'h> {
                                             'n' => res.push('\n'),
                                             't' => res.push('\t'),
                                             'r' => res.push('\r'),
+                                            '0' => res.push('\0'),
+                                            'u' => match self.read_unicode_escape() {
+                                                Ok(c) => res.push(c),
+                                                Err(err) => return reader_err!(err, self.current_line)
+                                            }
                                             '\n' => {self.current_line += 1;},
                                             _ => res.push(c),
                                         }
@@ -211,6 +259,11 @@ impl<'a, 'h> Reader<'a, 'h> {
                                                     'n' => '\n',
                                                     't' => '\t',
                                                     'r' => '\r',
+                                                    '0' => '\0',
+                                                    'u' => match self.read_unicode_escape() {
+                                                        Ok(c) => c,
+                                                        Err(err) => return reader_err!(err, self.current_line)
+                                                    }
                                                     c => c,
                                                 }),
                                                 None => return reader_err!("Unexpected end of input reading character literal", self.current_line)
@@ -277,7 +330,7 @@ impl<'a, 'h> Reader<'a, 'h> {
         }
     }
 
-    fn read_number(&mut self, chr: char) -> Result<f64, &'static str> {
+    fn read_number(&mut self, chr: char) -> Result<Token, &'static str> {
         let mut res: String = String::new();
         let mut next_char = chr;
         loop {
@@ -288,7 +341,7 @@ impl<'a, 'h> Reader<'a, 'h> {
                         next_char = *c;
                         self.chars.next();
                     }
-                    '.' => {
+                    '.' | '/' | '+' | '-' | 'i' => {
                         next_char = *c;
                         self.chars.next();
                     }
@@ -297,10 +350,59 @@ impl<'a, 'h> Reader<'a, 'h> {
                     | '(' | ')' | '{' | '}' | '[' | ']' => break,
                     _ => return Err("Unexpected character on number token"),
                 }
-                None => return Err("Unexpected end of input reading number"),
+                None => break,
+            }
+        }
+        // `a+bi`, `a-bi` and `bi` are complex numbers
+        if let Some(body) = res.strip_suffix('i') {
+            let split = body.rfind(['+', '-']).filter(|i| *i > 0);
+            let (re, im) = match split {
+                Some(i) => (&body[..i], &body[i..]),
+                None => ("0", body),
+            };
+            let im = match im {
+                "+" => "1",
+                "-" => "-1",
+                im => im,
+            };
+            return match (re.parse::<f64>(), im.parse::<f64>()) {
+                (Ok(re), Ok(im)) => Ok(Token::Complex(re, im)),
+                _ => Err("Invalid complex number"),
+            }
+        }
+        // `n/d` is an exact fraction
+        if let Some((n, d)) = res.split_once('/') {
+            return match (n.parse::<i128>(), d.parse::<i128>()) {
+                (Ok(_), Ok(0)) => Err("Fraction with a zero denominator"),
+                (Ok(n), Ok(d)) => Ok(Token::Rational(n, d)),
+                _ => Err("Invalid fraction"),
+            }
+        }
+        // integers a float can't hold exactly are kept as big integers
+        match res.parse::<i128>() {
+            Ok(n) => Ok(Token::Integer(n)),
+            Err(_) => res.parse::<f64>().map(Token::Number).map_err(|_| "Invalid number"),
+        }
+    }
+
+    /// Reads the `{XXXX}` part of an `\u{XXXX}` escape
+    fn read_unicode_escape(&mut self) -> Result<char, &'static str> {
+        if self.chars.next() != Some('{') {
+            return Err("Expected { in unicode escape")
+        }
+        let mut hex = String::new();
+        loop {
+            match self.chars.next() {
+                Some('}') => break,
+                Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+                Some(_) => return Err("Invalid character in unicode escape"),
+                None => return Err("Unexpected end of input reading unicode escape"),
             }
         }
-        return Ok(res.parse::<f64>().unwrap());
+        match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+            Some(c) => Ok(c),
+            None => Err("Invalid unicode escape"),
+        }
     }
 
     fn read_symbol(&mut self, chr: char) -> Result<String, &'static str> {
@@ -500,8 +602,14 @@ impl<'a, 'h> Reader<'a, 'h> {
             Token::True => ParserResult::Expr(Value::True),
             Token::False => ParserResult::Expr(Value::False),
             Token::Number(n) => ParserResult::Expr(Value::Num(n)),
+            Token::Rational(n, d) => ParserResult::Expr(numeric::rational(n, d)),
+            Token::Integer(n) => ParserResult::Expr(numeric::integer(n)),
+            Token::Complex(re, im) => ParserResult::Expr(Value::Complex(re, im)),
             Token::Char(c) => ParserResult::Expr(Value::Char(c)),
-            Token::String(s) => ParserResult::Expr(Value::Str(s.into())),
+            Token::String(s) => {
+                let string = self.strings.entry(s).or_insert_with_key(|s| s.as_str().into());
+                ParserResult::Expr(Value::Str(string.clone()))
+            },
             Token::Symbol(s) => ParserResult::Expr(Value::Sym(self.names.add(&s))),
             Token::Keyword(s) => ParserResult::Expr(Value::Keyword(self.names.add(&s))),
             _ => ParserResult::TokenErr("Invalid Syntax: Unexpected Token".to_string())
@@ -0,0 +1,268 @@
+/// Persistent hash map used by `Value::Map`
+///
+/// A hash array mapped trie: every level uses 5 bits of the key's hash to pick
+/// one of up to 32 children, stored compactly behind a bitmap. Cloning is O(1)
+/// and updates copy only the nodes on the path to the key, so `assoc` on a big
+/// map no longer copies every entry. Keys whose whole hash is equal share a
+/// collision node.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::rc::Rc;
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+#[derive(Clone)]
+enum Node<K, V> {
+    Branch(u32, Vec<Entry<K, V>>),
+    Collision(u64, Vec<(K, V)>),
+}
+
+#[derive(Clone)]
+enum Entry<K, V> {
+    Leaf(u64, K, V),
+    Node(Rc<Node<K, V>>),
+}
+
+#[derive(Clone)]
+pub struct PMap<K, V> {
+    root: Rc<Node<K, V>>,
+    len: usize,
+}
+
+fn hash_of<K: Hash>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Bit of the branch bitmap used by `hash` at the level starting at `shift`
+fn bit(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for PMap<K, V> {
+    fn default() -> Self {
+        PMap::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> PMap<K, V> {
+    pub fn new() -> Self {
+        PMap { root: Rc::new(Node::Branch(0, vec![])), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let hash = hash_of(key);
+        let mut node = &*self.root;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Branch(bitmap, children) => {
+                    let bit = bit(hash, shift);
+                    if bitmap & bit == 0 {
+                        return None
+                    }
+                    match &children[(bitmap & (bit - 1)).count_ones() as usize] {
+                        Entry::Leaf(h, k, v) => return if *h == hash && k == key { Some(v) } else { None },
+                        Entry::Node(next) => node = next,
+                    }
+                    shift += BITS;
+                }
+                Node::Collision(_, entries) => return entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Adds or replaces an entry, giving back the replaced value. Nodes shared
+    /// with other maps are copied before they change
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = insert(Rc::make_mut(&mut self.root), 0, hash_of(&key), key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = remove(Rc::make_mut(&mut self.root), 0, hash_of(key), key);
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let stack = match &*self.root {
+            Node::Branch(_, children) => vec![children.iter()],
+            Node::Collision(..) => unreachable!(),
+        };
+        Iter { stack, collision: [].iter() }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+fn insert<K: Eq + Clone, V: Clone>(node: &mut Node<K, V>, shift: u32, hash: u64, key: K, value: V) -> Option<V> {
+    match node {
+        Node::Branch(bitmap, children) => {
+            let bit = bit(hash, shift);
+            let index = (*bitmap & (bit - 1)).count_ones() as usize;
+            if *bitmap & bit == 0 {
+                *bitmap |= bit;
+                children.insert(index, Entry::Leaf(hash, key, value));
+                return None
+            }
+            match &mut children[index] {
+                Entry::Node(next) => insert(Rc::make_mut(next), shift + BITS, hash, key, value),
+                Entry::Leaf(h, k, v) if *h == hash && *k == key => Some(std::mem::replace(v, value)),
+                Entry::Leaf(h, k, v) => {
+                    let next = if *h == hash {
+                        Node::Collision(hash, vec![(k.clone(), v.clone()), (key, value)])
+                    } else {
+                        pair(shift + BITS, *h, Entry::Leaf(*h, k.clone(), v.clone()), hash, Entry::Leaf(hash, key, value))
+                    };
+                    children[index] = Entry::Node(Rc::new(next));
+                    None
+                }
+            }
+        }
+        Node::Collision(h, entries) => {
+            let h = *h;
+            if h == hash {
+                if let Some((_, v)) = entries.iter_mut().find(|(k, _)| *k == key) {
+                    return Some(std::mem::replace(v, value))
+                }
+                entries.push((key, value));
+            } else {
+                // a different hash with the same prefix, so the collision moves down a level
+                let old = Entry::Node(Rc::new(Node::Collision(h, std::mem::take(entries))));
+                *node = pair(shift, h, old, hash, Entry::Leaf(hash, key, value));
+            }
+            None
+        }
+    }
+}
+
+/// A branch at `shift` holding two entries whose hashes differ
+fn pair<K, V>(shift: u32, h1: u64, e1: Entry<K, V>, h2: u64, e2: Entry<K, V>) -> Node<K, V> {
+    let (b1, b2) = (bit(h1, shift), bit(h2, shift));
+    if b1 == b2 {
+        Node::Branch(b1, vec![Entry::Node(Rc::new(pair(shift + BITS, h1, e1, h2, e2)))])
+    } else if b1 < b2 {
+        Node::Branch(b1 | b2, vec![e1, e2])
+    } else {
+        Node::Branch(b1 | b2, vec![e2, e1])
+    }
+}
+
+fn remove<K: Eq + Clone, V: Clone>(node: &mut Node<K, V>, shift: u32, hash: u64, key: &K) -> Option<V> {
+    match node {
+        Node::Branch(bitmap, children) => {
+            let bit = bit(hash, shift);
+            if *bitmap & bit == 0 {
+                return None
+            }
+            let index = (*bitmap & (bit - 1)).count_ones() as usize;
+            let old = match &mut children[index] {
+                Entry::Leaf(h, k, _) if *h == hash && k == key => {
+                    *bitmap &= !bit;
+                    match children.remove(index) {
+                        Entry::Leaf(_, _, v) => return Some(v),
+                        Entry::Node(_) => unreachable!(),
+                    }
+                }
+                Entry::Leaf(..) => return None,
+                Entry::Node(next) => remove(Rc::make_mut(next), shift + BITS, hash, key)?,
+            };
+            // keep the trie compact by pulling single entries up into this branch
+            let single = match &children[index] {
+                Entry::Node(next) => match &**next {
+                    Node::Branch(_, entries) if entries.is_empty() => None,
+                    Node::Branch(_, entries) if entries.len() == 1 && matches!(entries[0], Entry::Leaf(..)) => Some(entries[0].clone()),
+                    Node::Collision(h, entries) if entries.len() == 1 => Some(Entry::Leaf(*h, entries[0].0.clone(), entries[0].1.clone())),
+                    _ => return Some(old),
+                },
+                Entry::Leaf(..) => return Some(old),
+            };
+            match single {
+                Some(entry) => children[index] = entry,
+                None => {
+                    *bitmap &= !bit;
+                    children.remove(index);
+                }
+            }
+            Some(old)
+        }
+        Node::Collision(_, entries) => {
+            let index = entries.iter().position(|(k, _)| k == key)?;
+            Some(entries.remove(index).1)
+        }
+    }
+}
+
+/// Walks the trie depth first, the order only depends on the hashes of the keys
+pub struct Iter<'a, K, V> {
+    stack: Vec<std::slice::Iter<'a, Entry<K, V>>>,
+    collision: std::slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            if let Some((k, v)) = self.collision.next() {
+                return Some((k, v))
+            }
+            match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                }
+                Some(Entry::Leaf(_, k, v)) => return Some((k, v)),
+                Some(Entry::Node(next)) => match &**next {
+                    Node::Branch(_, children) => self.stack.push(children.iter()),
+                    Node::Collision(_, entries) => self.collision = entries.iter(),
+                },
+            }
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone> IntoIterator for &'a PMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> FromIterator<(K, V)> for PMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = PMap::new();
+        for (k, v) in iter {
+            map.insert(k, v);
+        }
+        map
+    }
+}
@@ -1,4 +1,4 @@
-use crate::types::{Value};
+use crate::types::{Value, MapKey};
 use crate::names::{NamePool};
 use crate::error::Error;
 
@@ -9,6 +9,67 @@ impl Printer {
         Printer::repr_name_(value, 0, names)
     }
 
+    /// Like `repr_name` but without the leading quote, so `read` gives back the same value
+    pub fn repr_read(value: &Value, names: &NamePool) -> String {
+        Printer::repr_name_(value, 1, names)
+    }
+
+    /// Integral numbers print without a decimal point and nothing uses scientific
+    /// notation, so `1e21` prints all of its digits and `-0` prints as `0`
+    pub fn num(n: f64) -> String {
+        if n == 0.0 {
+            "0".to_string()
+        } else {
+            n.to_string()
+        }
+    }
+
+    /// Complex numbers print the way they are read, like `3+4i` or `1-0.5i`
+    fn complex(re: f64, im: f64) -> String {
+        let sign = if im < 0.0 || (im == 0.0 && im.is_sign_negative()) { "" } else { "+" };
+        format!("{}{}{}i", Printer::num(re), sign, Printer::num(im))
+    }
+
+    /// Vectors print as the call that builds them
+    fn vector(items: impl Iterator<Item = String>) -> String {
+        let mut res = String::from("(vector");
+        for item in items {
+            res.push(' ');
+            res.push_str(&item);
+        }
+        res.push(')');
+        res
+    }
+
+    fn map_key(key: &MapKey, names: &NamePool) -> String {
+        match key {
+            MapKey::Keyword(s) => format!(":{}", names.get(*s)),
+            MapKey::Sym(s) => format!("'{}", names.get(*s)),
+            MapKey::Str(s) => format!("\"{}\"", Printer::escape(&names.get(*s), '"')),
+        }
+    }
+
+    /// Escapes a string or char literal using only the escapes the reader understands
+    fn escape(s: &str, quote: char) -> String {
+        let mut res = String::with_capacity(s.len());
+        for ch in s.chars() {
+            match ch {
+                '\\' => res.push_str("\\\\"),
+                '\n' => res.push_str("\\n"),
+                '\t' => res.push_str("\\t"),
+                '\r' => res.push_str("\\r"),
+                '\0' => res.push_str("\\0"),
+                c if c == quote => {
+                    res.push('\\');
+                    res.push(c);
+                }
+                c if c.is_control() => res.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                c => res.push(c),
+            }
+        }
+        res
+    }
+
     fn repr_name_(value: &Value, level: i32, names: &NamePool) -> String {
         match value {
             Value::Keyword(s) => format!(":{}", names.get(*s)),
@@ -34,12 +95,13 @@ impl Printer {
                 res
             },
             Value::Box(val) => format!("(box {})", Printer::repr_name_(&(val.borrow()), level, names)),
+            Value::PVector(vector) => Printer::vector(vector.iter().map(|v| Printer::repr_name_(v, level, names))),
             Value::Lazy{data, ..} => format!("(cons* {} {})", Printer::repr_name_(&data.head, level, names), Printer::repr_name_(&data.tail, level, names)),
             Value::Map(map) => {
                 let mut res = String::new();
                 res.push_str("#[\n");
                 for (k, v) in map.iter() {
-                    res.push_str(&format!("{}:{} {}\n", (0..level+1).map(|_| "  ").collect::<String>(), names.get(*k), Printer::repr_name_(v, level+1, names)));
+                    res.push_str(&format!("{}{} {}\n", (0..level+1).map(|_| "  ").collect::<String>(), Printer::map_key(k, names), Printer::repr_name_(v, level+1, names)));
                 };
                 res.push_str(&format!("{}]", (0..level).map(|_| "  ").collect::<String>()));
                 res
@@ -54,12 +116,22 @@ impl Printer {
                 res
             }
             Value::StructDef(id) => format!("[Struct {}]", names.get(id.name)),
+            Value::File(file) => if file.borrow().is_some() {
+                "[File]".to_string()
+            } else {
+                "[Closed File]".to_string()
+            },
+            Value::Regex(regex) => format!("[Regex \"{}\"]", Printer::escape(&regex.source, '"')),
+            Value::StringBuilder(string) => format!("[StringBuilder \"{}\"]", Printer::escape(&string.borrow(), '"')),
             Value::Nil => format!("()"),
             Value::True => format!("#t"),
             Value::False => format!("#f"),
-            Value::Num(n) => format!("{}", n),
-            Value::Str(s) => format!("{:?}", s.inner()),
-            Value::Char(s) => format!("#{:?}", s),
+            Value::Num(n) => Printer::num(*n),
+            Value::Rational(n, d) => format!("{}/{}", n, d),
+            Value::BigInt(n) => n.to_string(),
+            Value::Complex(re, im) => Printer::complex(*re, *im),
+            Value::Str(s) => format!("\"{}\"", Printer::escape(s.inner(), '"')),
+            Value::Char(s) => format!("#'{}'", Printer::escape(&s.to_string(), '\'')),
             Value::NatFunc(_) => format!("[NativeFunction]"),
             Value::Func { func, .. } => if let Some(name) = &func.name {
                 format!("[Function {}]", names.get(*name))
@@ -74,9 +146,12 @@ impl Printer {
             Value::Nil => format!("()"),
             Value::True => format!("\x1b[95m#t\x1b[0m"),
             Value::False => format!("\x1b[95m#f\x1b[0m"),
-            Value::Num(n) => format!("\x1b[93m{}\x1b[0m", n),
-            Value::Str(s) => format!("\x1b[32m{:?}\x1b[0m", s.inner()),
-            Value::Char(s) => format!("\x1b[93m#{:?}\x1b[0m", s),
+            Value::Num(n) => format!("\x1b[93m{}\x1b[0m", Printer::num(*n)),
+            Value::Rational(n, d) => format!("\x1b[93m{}/{}\x1b[0m", n, d),
+            Value::BigInt(n) => format!("\x1b[93m{}\x1b[0m", n),
+            Value::Complex(re, im) => format!("\x1b[93m{}\x1b[0m", Printer::complex(*re, *im)),
+            Value::Str(s) => format!("\x1b[32m\"{}\"\x1b[0m", Printer::escape(s.inner(), '"')),
+            Value::Char(s) => format!("\x1b[93m#'{}'\x1b[0m", Printer::escape(&s.to_string(), '\'')),
             Value::Sym(s) => if level == 0 {
                     format!("'{}", names.get(*s))
                 } else {
@@ -110,12 +185,13 @@ impl Printer {
                 format!("\x1b[36m[Function]\x1b[0m")
             },
             Value::Box(val) => format!("(box {})", Printer::repr_color(&(val.borrow()), level, names)),
+            Value::PVector(vector) => Printer::vector(vector.iter().map(|v| Printer::repr_color(v, level, names))),
             Value::Lazy{data, ..} => format!("(cons* {} {})", Printer::repr_color(&data.head, level, names), Printer::repr_color(&data.tail, level, names)),
             Value::Map(map) => {
                 let mut res = String::new();
                 res.push_str("#[\n");
                 for (k, v) in map.iter() {
-                    res.push_str(&format!("{}:{} {}\n", (0..level+1).map(|_| "  ").collect::<String>(), names.get(*k), Printer::repr_color(v, level+1, names)));
+                    res.push_str(&format!("{}{} {}\n", (0..level+1).map(|_| "  ").collect::<String>(), Printer::map_key(k, names), Printer::repr_color(v, level+1, names)));
                 };
                 res.push_str(&format!("{}]", (0..level).map(|_| "  ").collect::<String>()));
                 res
@@ -130,6 +206,13 @@ impl Printer {
                 res
             }
             Value::StructDef(id) => format!("[Struct {}]", names.get(id.name)),
+            Value::File(file) => if file.borrow().is_some() {
+                "[File]".to_string()
+            } else {
+                "[Closed File]".to_string()
+            },
+            Value::Regex(regex) => format!("[Regex \"{}\"]", Printer::escape(&regex.source, '"')),
+            Value::StringBuilder(string) => format!("[StringBuilder \"{}\"]", Printer::escape(&string.borrow(), '"')),
         }
     }
 
@@ -151,15 +234,13 @@ impl Printer {
                 res
             },
             Value::Box(val) => format!("(box {})", Printer::repr_name(&(val.borrow()), names)),
+            Value::PVector(vector) => Printer::vector(vector.iter().map(|v| Printer::str_name(v, names))),
             Value::Lazy{data, ..} => format!("(cons* {} {})", Printer::repr_name(&data.head, names), Printer::repr_name(&data.tail, names)),
             Value::Map(map) => {
-                let mut res = String::new();
-                res.push_str("#[ ");
-                for (k, v) in map.iter() {
-                    res.push_str(&format!(":{} {}", names.get(*k), Printer::str_name(v, names)));
-                };
-                res.push_str("]");
-                res
+                let entries = map.iter()
+                    .map(|(k, v)| format!("{} {}", Printer::map_key(k, names), Printer::str_name(v, names)))
+                    .collect::<Vec<String>>();
+                format!("#[{}]", entries.join(" "))
             }
             Value::Struct(id, list) => {
                 let mut res = String::new();
@@ -171,10 +252,20 @@ impl Printer {
                 res
             }
             Value::StructDef(id) => format!("[Struct {}]", names.get(id.name)),
+            Value::File(file) => if file.borrow().is_some() {
+                "[File]".to_string()
+            } else {
+                "[Closed File]".to_string()
+            },
+            Value::Regex(regex) => format!("[Regex \"{}\"]", Printer::escape(&regex.source, '"')),
+            Value::StringBuilder(string) => format!("[StringBuilder \"{}\"]", Printer::escape(&string.borrow(), '"')),
             Value::Nil => format!("()"),
             Value::True => format!("#t"),
             Value::False => format!("#f"),
-            Value::Num(n) => format!("{}", n),
+            Value::Num(n) => Printer::num(*n),
+            Value::Rational(n, d) => format!("{}/{}", n, d),
+            Value::BigInt(n) => n.to_string(),
+            Value::Complex(re, im) => Printer::complex(*re, *im),
             Value::Str(s) => format!("{}", s.inner()),
             Value::Char(s) => format!("{}", s),
             Value::NatFunc(_) => format!("[NativeFunction]"),
@@ -228,8 +319,30 @@ impl Printer {
             } else {
                 format!("Thrown error")
             },
-            Error::Trace(name, err) => {
-                format!("{}\n\t at {}", Printer::str_error(err, names), names.get(*name))
+            Error::Custom(v) => match v {
+                Value::Struct(_, data) if data[1].is_nil() => Printer::repr_name(&data[0], names),
+                Value::Struct(_, data) => format!("{}: {}", Printer::repr_name(&data[0], names), Printer::str_name(&data[1], names)),
+                v => format!("Thrown value '{}'", Printer::repr_name(v, names)),
+            },
+            Error::Trace(_, _) => {
+                let (trace, err) = crate::error::collect_trace(value);
+                let mut res = Printer::str_error(err, names);
+                // deep recursion repeats the same frame, so runs of it are collapsed
+                let mut i = trace.len();
+                while i > 0 {
+                    let name = trace[i - 1];
+                    let mut count = 1;
+                    while i > count && trace[i - 1 - count] == name {
+                        count += 1;
+                    }
+                    if count == 1 {
+                        res.push_str(&format!("\n\t at {}", names.get(name)));
+                    } else {
+                        res.push_str(&format!("\n\t at {} ({} times)", names.get(name), count));
+                    }
+                    i -= count;
+                }
+                res
             }
         }
     }
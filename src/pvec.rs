@@ -0,0 +1,186 @@
+/// Persistent vector used by `Value::PVector`
+///
+/// A 32-way bit-partitioned trie with the last (partial) leaf kept apart as the
+/// tail, so `push` is amortized O(1) and `get`/`set` are O(log32 n). Updates copy
+/// only the path to the changed leaf and share the rest with the original.
+use std::rc::Rc;
+use std::iter::FromIterator;
+
+use crate::types::Value;
+
+const BITS: u32 = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+enum Node {
+    Branch(Vec<Rc<Node>>),
+    Leaf(Vec<Value>),
+}
+
+#[derive(Clone)]
+pub struct PVector {
+    len: usize,
+    shift: u32,
+    root: Rc<Node>,
+    tail: Rc<Vec<Value>>,
+}
+
+impl Default for PVector {
+    fn default() -> Self {
+        PVector::new()
+    }
+}
+
+impl PVector {
+    pub fn new() -> Self {
+        PVector {
+            len: 0,
+            shift: BITS,
+            root: Rc::new(Node::Branch(vec![])),
+            tail: Rc::new(vec![]),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Index of the first element stored in the tail
+    fn tail_offset(&self) -> usize {
+        if self.len < WIDTH {
+            0
+        } else {
+            ((self.len - 1) >> BITS) << BITS
+        }
+    }
+
+    /// The leaf holding index `i`, which must be in bounds
+    fn leaf(&self, i: usize) -> &[Value] {
+        if i >= self.tail_offset() {
+            return &self.tail
+        }
+        let mut node = &self.root;
+        let mut level = self.shift;
+        loop {
+            match &**node {
+                Node::Branch(children) => {
+                    node = &children[(i >> level) & MASK];
+                    level -= BITS;
+                }
+                Node::Leaf(values) => return values,
+            }
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Value> {
+        if i < self.len {
+            Some(&self.leaf(i)[i & MASK])
+        } else {
+            None
+        }
+    }
+
+    /// A new vector with `value` added at the end
+    pub fn push(&self, value: Value) -> PVector {
+        if self.len - self.tail_offset() < WIDTH {
+            let mut tail = (*self.tail).clone();
+            tail.push(value);
+            return PVector { tail: Rc::new(tail), len: self.len + 1, ..self.clone() }
+        }
+        // the tail is full, so it moves into the trie and a new one is started
+        let leaf = Rc::new(Node::Leaf((*self.tail).clone()));
+        let (root, shift) = if (self.len >> BITS) > (1 << self.shift) {
+            let root = Node::Branch(vec![self.root.clone(), new_path(self.shift, leaf)]);
+            (Rc::new(root), self.shift + BITS)
+        } else {
+            (self.push_leaf(self.shift, &self.root, leaf), self.shift)
+        };
+        PVector { len: self.len + 1, shift, root, tail: Rc::new(vec![value]) }
+    }
+
+    fn push_leaf(&self, level: u32, parent: &Rc<Node>, leaf: Rc<Node>) -> Rc<Node> {
+        let mut children = match &**parent {
+            Node::Branch(children) => children.clone(),
+            Node::Leaf(_) => unreachable!(),
+        };
+        let index = ((self.len - 1) >> level) & MASK;
+        if level == BITS {
+            children.push(leaf);
+        } else if index < children.len() {
+            children[index] = self.push_leaf(level - BITS, &children[index], leaf);
+        } else {
+            children.push(new_path(level - BITS, leaf));
+        }
+        Rc::new(Node::Branch(children))
+    }
+
+    /// A new vector with the value at `i` replaced, `None` if `i` is out of bounds
+    pub fn set(&self, i: usize, value: Value) -> Option<PVector> {
+        if i >= self.len {
+            return None
+        }
+        if i >= self.tail_offset() {
+            let mut tail = (*self.tail).clone();
+            tail[i & MASK] = value;
+            return Some(PVector { tail: Rc::new(tail), ..self.clone() })
+        }
+        Some(PVector { root: set_in(&self.root, self.shift, i, value), ..self.clone() })
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { vector: self, index: 0, leaf: &[] }
+    }
+}
+
+fn new_path(level: u32, node: Rc<Node>) -> Rc<Node> {
+    if level == 0 {
+        node
+    } else {
+        Rc::new(Node::Branch(vec![new_path(level - BITS, node)]))
+    }
+}
+
+fn set_in(node: &Rc<Node>, level: u32, i: usize, value: Value) -> Rc<Node> {
+    match &**node {
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let index = (i >> level) & MASK;
+            children[index] = set_in(&children[index], level - BITS, i, value);
+            Rc::new(Node::Branch(children))
+        }
+        Node::Leaf(values) => {
+            let mut values = values.clone();
+            values[i & MASK] = value;
+            Rc::new(Node::Leaf(values))
+        }
+    }
+}
+
+/// Walks the vector a leaf at a time instead of looking up every index
+pub struct Iter<'a> {
+    vector: &'a PVector,
+    index: usize,
+    leaf: &'a [Value],
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<&'a Value> {
+        if self.index >= self.vector.len {
+            return None
+        }
+        if self.index & MASK == 0 {
+            self.leaf = self.vector.leaf(self.index);
+        }
+        let value = &self.leaf[self.index & MASK];
+        self.index += 1;
+        Some(value)
+    }
+}
+
+impl FromIterator<Value> for PVector {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        iter.into_iter().fold(PVector::new(), |vector, value| vector.push(value))
+    }
+}
@@ -0,0 +1,460 @@
+use std::cell::Cell;
+
+/// Small backtracking regular expression engine used by the `re-*` functions
+///
+/// Supports literals, `.`, classes (`[a-z]`, `[^0-9]`), `\d \w \s` and their
+/// negations, anchors `^ $ \b \B`, capturing and `(?:...)` groups, alternation
+/// and the greedy or lazy quantifiers `* + ? {n} {n,} {n,m}`.
+pub struct Regex {
+    pub source: String,
+    node: Node,
+    groups: usize,
+    /// Nesting of `node_match` in the match being tried
+    depth: Cell<usize>,
+    /// Set when a match gave up because it nested too deep
+    too_deep: Cell<bool>,
+}
+
+/// Deepest `node_match` nesting allowed before a match fails with an error
+/// instead of overflowing the stack, repeating a group nests a few levels
+/// per repetition
+const MAX_DEPTH: usize = 4000;
+
+/// Start and end (byte offsets) of each capture group, group 0 is the whole match
+pub type Captures = Vec<Option<(usize, usize)>>;
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    WordBoundary(bool),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>, bool),
+}
+
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            ClassItem::Range(a, b) => *a <= c && c <= *b,
+            ClassItem::Digit(neg) => c.is_ascii_digit() != *neg,
+            ClassItem::Word(neg) => is_word(c) != *neg,
+            ClassItem::Space(neg) => c.is_whitespace() != *neg,
+        }
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    groups: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_alt(&mut self) -> Result<Node, String> {
+        let mut alts = vec![self.parse_concat()?];
+        while let Some('|') = self.chars.peek() {
+            self.chars.next();
+            alts.push(self.parse_concat()?);
+        }
+        Ok(if alts.len() == 1 { alts.pop().unwrap() } else { Node::Alt(alts) })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![];
+        loop {
+            match self.chars.peek() {
+                None | Some('|') | Some(')') => break,
+                _ => nodes.push(self.parse_repeat()?),
+            }
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn parse_repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_atom()?;
+        loop {
+            let (min, max) = match self.chars.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    self.chars.next();
+                    let min = self.parse_count()?.ok_or("expected a number after {")?;
+                    let max = match self.chars.next() {
+                        Some('}') => Some(min),
+                        Some(',') => {
+                            let max = self.parse_count()?;
+                            if self.chars.next() != Some('}') {
+                                return Err("expected } closing the repetition".to_string())
+                            }
+                            max
+                        }
+                        _ => return Err("expected } closing the repetition".to_string()),
+                    };
+                    if max.is_some_and(|max| max < min) {
+                        return Err("invalid repetition range".to_string())
+                    }
+                    node = self.finish_repeat(node, min, max);
+                    continue
+                }
+                _ => return Ok(node),
+            };
+            self.chars.next();
+            node = self.finish_repeat(node, min, max);
+        }
+    }
+
+    fn finish_repeat(&mut self, node: Node, min: usize, max: Option<usize>) -> Node {
+        let greedy = if let Some('?') = self.chars.peek() {
+            self.chars.next();
+            false
+        } else {
+            true
+        };
+        Node::Repeat(Box::new(node), min, max, greedy)
+    }
+
+    fn parse_count(&mut self) -> Result<Option<usize>, String> {
+        let mut digits = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break
+            }
+            digits.push(*c);
+            self.chars.next();
+        }
+        if digits.is_empty() {
+            return Ok(None)
+        }
+        digits.parse().map(Some).map_err(|_| "repetition count is too big".to_string())
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        match self.chars.next() {
+            Some('(') => {
+                let index = if let Some('?') = self.chars.peek() {
+                    self.chars.next();
+                    if self.chars.next() != Some(':') {
+                        return Err("expected : after (?".to_string())
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let node = self.parse_alt()?;
+                if self.chars.next() != Some(')') {
+                    return Err("unclosed group".to_string())
+                }
+                Ok(Node::Group(Box::new(node), index))
+            }
+            Some('[') => self.parse_class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.chars.next() {
+                Some('b') => Ok(Node::WordBoundary(true)),
+                Some('B') => Ok(Node::WordBoundary(false)),
+                Some(c) => Ok(match Parser::class_escape(c) {
+                    Some(item) => Node::Class(vec![item], false),
+                    None => Node::Char(Parser::char_escape(c)),
+                }),
+                None => Err("pattern ends with \\".to_string()),
+            },
+            Some(c @ '*') | Some(c @ '+') | Some(c @ '?') | Some(c @ '{') => Err(format!("nothing to repeat before {}", c)),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node, String> {
+        let negated = if let Some('^') = self.chars.peek() {
+            self.chars.next();
+            true
+        } else {
+            false
+        };
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let c = match self.chars.next() {
+                Some(']') if !first => break,
+                Some('\\') => match self.chars.next() {
+                    Some(c) => match Parser::class_escape(c) {
+                        Some(item) => {
+                            items.push(item);
+                            first = false;
+                            continue
+                        }
+                        None => Parser::char_escape(c),
+                    },
+                    None => return Err("unclosed character class".to_string()),
+                },
+                Some(c) => c,
+                None => return Err("unclosed character class".to_string()),
+            };
+            first = false;
+            let mut lookahead = self.chars.clone();
+            if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|c| *c != ']') {
+                self.chars.next();
+                let end = match self.chars.next() {
+                    Some('\\') => Parser::char_escape(self.chars.next().ok_or("unclosed character class")?),
+                    Some(end) => end,
+                    None => return Err("unclosed character class".to_string()),
+                };
+                if end < c {
+                    return Err(format!("invalid class range {}-{}", c, end))
+                }
+                items.push(ClassItem::Range(c, end));
+            } else {
+                items.push(ClassItem::Range(c, c));
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+
+    fn class_escape(c: char) -> Option<ClassItem> {
+        match c {
+            'd' => Some(ClassItem::Digit(false)),
+            'D' => Some(ClassItem::Digit(true)),
+            'w' => Some(ClassItem::Word(false)),
+            'W' => Some(ClassItem::Word(true)),
+            's' => Some(ClassItem::Space(false)),
+            'S' => Some(ClassItem::Space(true)),
+            _ => None,
+        }
+    }
+
+    fn char_escape(c: char) -> char {
+        match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            c => c,
+        }
+    }
+}
+
+type Cont<'k> = &'k mut dyn FnMut(usize, &mut Captures) -> bool;
+
+fn char_at(input: &str, pos: usize) -> Option<char> {
+    input[pos..].chars().next()
+}
+
+fn char_before(input: &str, pos: usize) -> Option<char> {
+    input[..pos].chars().next_back()
+}
+
+impl Regex {
+    /// Compiles a pattern, the error describes what is wrong with it
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser { chars: pattern.chars().peekable(), groups: 0 };
+        let node = parser.parse_alt()?;
+        if parser.chars.next().is_some() {
+            return Err("unmatched )".to_string())
+        }
+        Ok(Regex { source: pattern.to_string(), node, groups: parser.groups, depth: Cell::new(0), too_deep: Cell::new(false) })
+    }
+
+    /// Number of capture groups, not counting the whole match
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    /// Tries to match starting exactly at `start`, fails if the match nests too deep
+    pub fn match_at(&self, input: &str, start: usize) -> Result<Option<Captures>, String> {
+        let mut caps: Captures = vec![None; self.groups + 1];
+        let mut end = None;
+        let matched = self.guarded(|| self.node_match(&self.node, input, start, &mut caps, &mut |pos, _| { end = Some(pos); true }))?;
+        if matched {
+            caps[0] = Some((start, end.unwrap()));
+            Ok(Some(caps))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Finds the leftmost match starting at or after `start`, which must be a char boundary
+    pub fn find_at(&self, input: &str, start: usize) -> Result<Option<Captures>, String> {
+        let starts = input[start..].char_indices().map(|(i, _)| start + i).chain(std::iter::once(input.len()));
+        for pos in starts {
+            if let Some(caps) = self.match_at(input, pos)? {
+                return Ok(Some(caps))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Where to search after a match, empty matches skip a char so the search moves on.
+    /// Goes past the end of the input once there is nothing left to search
+    pub fn next_start(&self, input: &str, caps: &Captures) -> usize {
+        let (start, end) = caps[0].unwrap();
+        if start != end {
+            end
+        } else {
+            end + char_at(input, end).map_or(1, char::len_utf8)
+        }
+    }
+
+    /// Matches the whole input
+    pub fn match_full(&self, input: &str) -> Result<Option<Captures>, String> {
+        let mut caps: Captures = vec![None; self.groups + 1];
+        let len = input.len();
+        if self.guarded(|| self.node_match(&self.node, input, 0, &mut caps, &mut |pos, _| pos == len))? {
+            caps[0] = Some((0, len));
+            Ok(Some(caps))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Runs one match attempt, turning a match that nested too deep into an error
+    fn guarded(&self, attempt: impl FnOnce() -> bool) -> Result<bool, String> {
+        self.depth.set(0);
+        self.too_deep.set(false);
+        let matched = attempt();
+        if self.too_deep.get() {
+            return Err(format!("the input is too long to match against {:?}", self.source))
+        }
+        Ok(matched)
+    }
+
+    fn node_match(&self, node: &Node, input: &str, pos: usize, caps: &mut Captures, k: Cont) -> bool {
+        let depth = self.depth.get();
+        if depth >= MAX_DEPTH || self.too_deep.get() {
+            self.too_deep.set(true);
+            return false
+        }
+        self.depth.set(depth + 1);
+        let matched = self.node_match_inner(node, input, pos, caps, k);
+        self.depth.set(depth);
+        matched
+    }
+
+    fn node_match_inner(&self, node: &Node, input: &str, pos: usize, caps: &mut Captures, k: Cont) -> bool {
+        match node {
+            Node::Char(_) | Node::Any | Node::Class(..) => match Regex::step(node, input, pos) {
+                Some(next) => k(next, caps),
+                None => false,
+            },
+            Node::Start => pos == 0 && k(pos, caps),
+            Node::End => pos == input.len() && k(pos, caps),
+            Node::WordBoundary(expected) => {
+                let before = char_before(input, pos).is_some_and(is_word);
+                let after = char_at(input, pos).is_some_and(is_word);
+                (before != after) == *expected && k(pos, caps)
+            }
+            Node::Group(inner, index) => match index {
+                Some(i) => {
+                    let i = *i;
+                    let old = caps[i];
+                    let matched = self.node_match(inner, input, pos, caps, &mut |end, caps: &mut Captures| {
+                        let inner_old = caps[i];
+                        caps[i] = Some((pos, end));
+                        if k(end, caps) {
+                            true
+                        } else {
+                            caps[i] = inner_old;
+                            false
+                        }
+                    });
+                    if !matched {
+                        caps[i] = old;
+                    }
+                    matched
+                }
+                None => self.node_match(inner, input, pos, caps, k),
+            },
+            Node::Concat(nodes) => self.concat_match(nodes, input, pos, caps, k),
+            Node::Alt(alts) => alts.iter().any(|alt| self.node_match(alt, input, pos, caps, k)),
+            Node::Repeat(inner, min, max, greedy) => self.repeat_match(inner, *min, *max, *greedy, 0, input, pos, caps, k),
+        }
+    }
+
+    fn concat_match(&self, nodes: &[Node], input: &str, pos: usize, caps: &mut Captures, k: Cont) -> bool {
+        match nodes.split_first() {
+            None => k(pos, caps),
+            Some((first, rest)) => self.node_match(first, input, pos, caps, &mut |next, caps: &mut Captures| {
+                self.concat_match(rest, input, next, caps, k)
+            }),
+        }
+    }
+
+    /// Where a node matching exactly one char ends when it matches at `pos`
+    fn step(node: &Node, input: &str, pos: usize) -> Option<usize> {
+        let ch = char_at(input, pos)?;
+        let matched = match node {
+            Node::Char(c) => ch == *c,
+            Node::Any => ch != '\n',
+            Node::Class(items, negated) => items.iter().any(|item| item.matches(ch)) != *negated,
+            _ => unreachable!(),
+        };
+        if matched { Some(pos + ch.len_utf8()) } else { None }
+    }
+
+    /// Repeats a node matching one char in a loop, recursing once per char
+    /// would overflow the stack on long inputs
+    #[allow(clippy::too_many_arguments)]
+    fn repeat_single(&self, node: &Node, min: usize, max: Option<usize>, greedy: bool,
+                     input: &str, pos: usize, caps: &mut Captures, k: Cont) -> bool {
+        if greedy {
+            let mut ends = vec![pos];
+            while max.is_none_or(|max| ends.len() <= max) {
+                match Regex::step(node, input, *ends.last().unwrap()) {
+                    Some(next) => ends.push(next),
+                    None => break,
+                }
+            }
+            return ends.len() > min && ends[min..].iter().rev().any(|end| k(*end, caps))
+        }
+        let (mut count, mut end) = (0, pos);
+        loop {
+            if count >= min && k(end, caps) {
+                return true
+            }
+            if max.is_some_and(|max| count >= max) {
+                return false
+            }
+            match Regex::step(node, input, end) {
+                Some(next) => {
+                    end = next;
+                    count += 1;
+                }
+                None => return false,
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat_match(&self, node: &Node, min: usize, max: Option<usize>, greedy: bool, count: usize,
+                    input: &str, pos: usize, caps: &mut Captures, k: Cont) -> bool {
+        if count == 0 && matches!(node, Node::Char(_) | Node::Any | Node::Class(..)) {
+            return self.repeat_single(node, min, max, greedy, input, pos, caps, k)
+        }
+        let can_repeat = max.is_none_or(|max| count < max);
+        let once_more = |caps: &mut Captures, k: Cont| can_repeat && self.node_match(node, input, pos, caps, &mut |next, caps: &mut Captures| {
+            // an empty iteration past the minimum would loop forever
+            (next != pos || count < min) && self.repeat_match(node, min, max, greedy, count + 1, input, next, caps, k)
+        });
+        match (count < min, greedy) {
+            (true, _) => once_more(caps, k),
+            (false, true) => once_more(caps, k) || k(pos, caps),
+            (false, false) => k(pos, caps) || once_more(caps, k),
+        }
+    }
+}
@@ -2,9 +2,14 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::collections::HashMap;
 use std::ops::{Deref};
+use std::fs::File;
+use std::io::BufReader;
 
 use crate::error::{Error};
 use crate::names::{Name, NamePool};
+use crate::regex::Regex;
+use crate::pvec::PVector;
+use crate::pmap::PMap;
 
 pub struct FuncData {
     pub ast: Value,
@@ -14,6 +19,8 @@ pub struct FuncData {
     pub rest_param: Option<Name>,
     pub is_macro: bool,
     pub name: Option<Name>,
+    /// Docstring given as the first body form of `fun` or `defmacro`
+    pub doc: Option<String>,
     pub arity: Arity,
     pub names: Rc<NamePool>,
 }
@@ -22,6 +29,88 @@ pub struct LazyData {
     pub head: Value,
     pub tail: Value,
     pub names: Rc<NamePool>,
+    /// The evaluated tail, kept so it is only computed once
+    pub realized: RefCell<Option<Value>>,
+}
+
+impl LazyData {
+    pub fn new(head: Value, tail: Value, names: Rc<NamePool>) -> Rc<LazyData> {
+        Rc::new(LazyData { head, tail, names, realized: RefCell::new(None) })
+    }
+
+    /// Evaluates the tail the first time it is needed, later calls reuse the result
+    pub fn force_tail(&self, env: &Env, eval: fn(Value, Env, Rc<NamePool>) -> ValueResult) -> ValueResult {
+        let realized = self.realized.borrow().clone();
+        if let Some(tail) = realized {
+            return Ok(tail)
+        }
+        let tail = match &self.tail {
+            // tails built by native functions call a native function on values that
+            // are already evaluated, calling it directly skips the dispatch in eval
+            Value::List(l) if LazyData::is_native_call(l) => l[0].apply(l[1..].to_vec(), &self.names)?,
+            tail => eval(tail.clone(), env.clone(), self.names.clone())?,
+        };
+        *self.realized.borrow_mut() = Some(tail.clone());
+        Ok(tail)
+    }
+
+    fn is_native_call(list: &SliceList) -> bool {
+        matches!(list.first(), Some(Value::NatFunc(_)))
+            && list[1..].iter().all(|arg| !matches!(arg, Value::List(_) | Value::Sym(_)))
+    }
+
+    pub fn is_realized(&self) -> bool {
+        self.realized.borrow().is_some()
+    }
+}
+
+/// Realized tails are unlinked one at a time, dropping a long sequence
+/// recursively would overflow the stack
+impl Drop for LazyData {
+    fn drop(&mut self) {
+        let mut next = self.realized.get_mut().take();
+        while let Some(Value::Lazy { data, .. }) = next {
+            next = match Rc::try_unwrap(data) {
+                Ok(mut data) => data.realized.get_mut().take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// Key of a map, remembers if it was a keyword, a symbol or a string so keys
+/// with the same name but a different kind stay apart
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum MapKey {
+    Keyword(Name),
+    Sym(Name),
+    Str(Name),
+}
+
+impl MapKey {
+    /// Strings are interned into string keys
+    pub fn from_value(value: &Value, names: &NamePool) -> Option<MapKey> {
+        match value {
+            Value::Keyword(s) => Some(MapKey::Keyword(*s)),
+            Value::Sym(s) => Some(MapKey::Sym(*s)),
+            Value::Str(s) => Some(MapKey::Str(names.add(s.inner()))),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> Name {
+        match self {
+            MapKey::Keyword(s) | MapKey::Sym(s) | MapKey::Str(s) => s,
+        }
+    }
+
+    pub fn to_value(self, names: &NamePool) -> Value {
+        match self {
+            MapKey::Keyword(s) => Value::Keyword(s),
+            MapKey::Sym(s) => Value::Sym(s),
+            MapKey::Str(s) => Value::Str(names.get(s).into()),
+        }
+    }
 }
 
 pub struct StructData {
@@ -54,6 +143,12 @@ pub enum Value {
     False,
     /// Number value is 64 bit float
     Num(f64),
+    /// Exact fraction, reduced with a positive denominator that isn't 1
+    Rational(i64, i64),
+    /// Integer too big for a float to hold exactly
+    BigInt(i128),
+    /// Complex number with its real and imaginary parts
+    Complex(f64, f64),
     /// Char value
     Char(char),
     /// String value
@@ -80,9 +175,17 @@ pub enum Value {
         eval: fn(Value, Env, Rc<NamePool>) -> Result<Value, Error>,
         data: Rc<LazyData>,
     },
-    Map(Rc<HashMap<Name, Value>>),
+    /// Persistent hash map, `assoc` and `dissoc` share structure with the original
+    Map(PMap<MapKey, Value>),
     StructDef(Rc<StructData>),
-    Struct(Rc<StructData>, Rc<Vec<Value>>)
+    Struct(Rc<StructData>, Rc<Vec<Value>>),
+    /// File handle opened for reading, `None` once it has been closed
+    File(Rc<RefCell<Option<BufReader<File>>>>),
+    Regex(Rc<Regex>),
+    /// Persistent vector, updates share structure with the original
+    PVector(Rc<PVector>),
+    /// Mutable string, appending to it doesn't copy what's already there
+    StringBuilder(Rc<RefCell<String>>),
 }
 
 pub type ValueList = Vec<Value>;
@@ -96,6 +199,10 @@ impl PartialEq for Value {
             (True, True) => true,
             (False, False) => true,
             (Num(a), Num(b)) => a == b,
+            (Rational(a, b), Rational(c, d)) => a == c && b == d,
+            (BigInt(a), BigInt(b)) => a == b,
+            (Complex(a, b), Complex(c, d)) => a == c && b == d,
+            (Char(a), Char(b)) => a == b,
             (Str(a), Str(b)) => a == b,
             (Sym(a), Sym(b)) => a == b,
             (Keyword(a), Keyword(b)) => a == b,
@@ -108,6 +215,11 @@ impl PartialEq for Value {
             (NatFunc(a), NatFunc(b)) => a.name == b.name,
             (Box(a), Box(b)) => Rc::ptr_eq(a, b),
             (StructDef(a), StructDef(b)) => Rc::ptr_eq(a, b),
+            (File(a), File(b)) => Rc::ptr_eq(a, b),
+            (StringBuilder(a), StringBuilder(b)) => Rc::ptr_eq(a, b),
+            (Regex(a), Regex(b)) => a.source == b.source,
+            (PVector(a), PVector(b)) => a.len() == b.len() && a.iter().eq(b.iter()),
+            (Map(a), Map(b)) => a.len() == b.len() && a.iter().all(|(k, v)| b.get(k) == Some(v)),
             _ => false,
         }
     }
@@ -139,11 +251,7 @@ impl Value {
     pub fn apply(&self, args: ValueList, names: &NamePool) -> ValueResult {
         match self {
             Value::NatFunc(f) => {
-                if !match f.arity {
-                    Arity::Exact(n) => args.len() == n.into(),
-                    Arity::Min(n) => args.len() >= n.into(),
-                    Arity::Range(min, max) => min as usize <= args.len() && args.len() <= max.into(),
-                } {
+                if !f.arity.accepts(args.len()) {
                     return Err(Error::ArgErr(Some(f.name), f.arity.clone(), args.len() as u16))
                 }
                 match (f.func)(args, names) {
@@ -174,6 +282,16 @@ impl Value {
                     }
                 })
             },
+            // a keyword looks itself up in a map, giving the default or nil when missing
+            Value::Keyword(key) => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(Error::ArgErr(Some(*key), Arity::Range(1, 2), args.len() as u16))
+                }
+                match &args[0] {
+                    Value::Map(map) => Ok(map.get(&MapKey::Keyword(*key)).or_else(|| args.get(1)).cloned().unwrap_or(Value::Nil)),
+                    x => Err(Error::TypeErr("map", Some(x.clone()))),
+                }
+            }
             _ => Err(Error::CallErr(Some(self.clone()))),
         }
     }
@@ -201,6 +319,7 @@ impl Value {
             Value::Nil => Ok(Value::Nil),
             Value::Str(s) => Ok(s.head().into()),
             Value::Lazy{data, ..} => Ok(data.head.clone()),
+            Value::PVector(v) => Ok(v.get(0).cloned().into()),
             x => Err(Error::TypeErr("collection", Some(x.clone()))),
         }
     }
@@ -210,7 +329,9 @@ impl Value {
             Value::List(l) => Ok(l.tail().into()),
             Value::Str(s) => Ok(s.tail().into()),
             Value::Nil => Ok(Value::Nil),
-            Value::Lazy{eval, data, env} => eval(data.tail.clone(), env.clone(), data.names.clone()),
+            Value::Lazy{eval, data, env} => data.force_tail(env, *eval),
+            // walking on from here is done on a list, so only the first rest copies
+            Value::PVector(v) => Ok(v.iter().skip(1).cloned().collect::<ValueList>().into()),
             x => Err(Error::TypeErr("collection", Some(x.clone())))
         }
     }
@@ -243,6 +364,17 @@ pub fn func(name: Name, arity: Arity, func: fn(ValueList, &NamePool) -> Result<V
     Value::NatFunc(NatFunc { name, func, arity })
 }
 
+impl Arity {
+    /// Checks if a call with `n` arguments is allowed
+    pub fn accepts(&self, n: usize) -> bool {
+        match *self {
+            Arity::Exact(exact) => n == exact.into(),
+            Arity::Min(min) => n >= min.into(),
+            Arity::Range(min, max) => min as usize <= n && n <= max.into(),
+        }
+    }
+}
+
 impl std::fmt::Display for Arity {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self {
@@ -277,11 +409,7 @@ impl EnvStruct {
         let opt_len = opt.len() * if *keys { 2 } else { 1 };
         let args_len = exprs.len();
 
-        if !match func.arity {
-            Arity::Exact(n) => args_len == n.into(),
-            Arity::Min(n) => args_len >= n.into(),
-            Arity::Range(min, max) => min as usize <= args_len && args_len <= max.into(),
-        } {
+        if !func.arity.accepts(args_len) {
             return Err(Error::ArgErr(func.name.clone(), func.arity.clone(), args_len as u16))
         }
 
@@ -425,7 +553,7 @@ impl SliceString {
     }
 
     pub fn tail(&self) -> Option<SliceString> {
-        match self.string.chars().next() {
+        match self.head() {
             Some(ch) => {
                 if self.start + ch.len_utf8() == self.string.len() {
                     return None